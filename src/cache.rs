@@ -0,0 +1,294 @@
+use anyhow::{Context, Result, bail};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tower_lsp::lsp_types::{Range, SymbolKind, Url};
+
+use crate::index::{ReferenceIndex, SymbolEntry, SymbolIndex};
+use crate::state::crc32c;
+
+const MAGIC: &[u8; 8] = b"PARSECSY";
+// Bumped whenever the layout changes, and also when symbol extraction starts
+// recording different symbols, so unchanged files are not served stale ones.
+const FORMAT_VERSION: u32 = 7;
+
+pub const CACHE_FILE: &str = "symbols.bin";
+
+#[derive(Serialize, Deserialize)]
+struct CachedSymbol {
+    name: String,
+    uri: Url,
+    path: PathBuf,
+    range: Range,
+    kind: SymbolKind,
+    container: Option<String>,
+    signature: Option<String>,
+    supertype: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDoc {
+    uri: Url,
+    // Modification time of the file when its symbols were extracted; a doc
+    // whose file has changed since is dropped on load.
+    mtime: Option<(u64, u32)>,
+    symbols: Vec<CachedSymbol>,
+    // Identifier occurrences as (name, range, is_declaration), so an
+    // unchanged file needs no parse at all on the next start.
+    occurrences: Vec<(String, Range, bool)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    docs: Vec<CachedDoc>,
+}
+
+// Caches live in the user cache directory rather than in the workspace, one
+// directory per root: its name plus a hash of its canonical path, so two
+// checkouts called `MyPkg` do not share a cache. None when the platform has
+// no cache directory.
+pub fn cache_path(root: &Path) -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("", "", "parsec")?;
+    let canonical = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let name = canonical
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let hash = crc32c(0, canonical.as_os_str().as_encoded_bytes());
+    Some(
+        dirs.cache_dir()
+            .join("symbols")
+            .join(format!("{name}-{hash:08x}"))
+            .join(CACHE_FILE),
+    )
+}
+
+// Returns whether there was a cache file to remove.
+pub fn clear(root: &Path) -> Result<bool> {
+    let Some(path) = cache_path(root) else {
+        return Ok(false);
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("removing {}", path.display())),
+    }
+}
+
+// `mtime` gives the modification time each document's file had when it was
+// read for indexing; documents it has none for are not written. Load checks
+// that time against the file, so a document indexed from an unsaved editor
+// buffer must get None: its symbols would outlive the buffer.
+pub fn save(
+    index: &SymbolIndex,
+    references: &ReferenceIndex,
+    path: &Path,
+    mtime: impl Fn(&str) -> Option<(u64, u32)>,
+) -> Result<()> {
+    // A file can have occurrences without any symbols, and the other way
+    // round for entries that came from the cache's own load.
+    let mut by_doc: BTreeMap<String, Vec<SymbolEntry>> = references
+        .doc_uris()
+        .into_iter()
+        .map(|uri| (uri, Vec::new()))
+        .collect();
+    for (uri, entries) in index.snapshot() {
+        by_doc.insert(uri, entries.to_vec());
+    }
+    let mut docs = Vec::new();
+    for (key, entries) in by_doc {
+        let Ok(uri) = Url::parse(&key) else {
+            continue;
+        };
+        let Some(mtime) = mtime(&key) else {
+            continue;
+        };
+        let symbols = entries
+            .iter()
+            .map(|e| CachedSymbol {
+                name: e.name.to_string(),
+                uri: e.uri.clone(),
+                path: e.path.clone(),
+                range: e.range,
+                kind: e.kind,
+                container: e.container.as_deref().map(str::to_string),
+                signature: e.signature.as_deref().map(str::to_string),
+                supertype: e.supertype.as_deref().map(str::to_string),
+            })
+            .collect();
+        docs.push(CachedDoc {
+            uri,
+            mtime: Some(mtime),
+            symbols,
+            occurrences: references.occurrences(&key),
+        });
+    }
+
+    let mut bytes = Vec::with_capacity(64 * 1024);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    let body = bincode::serde::encode_to_vec(&CacheFile { docs }, bincode::config::standard())?;
+    bytes.extend_from_slice(&body);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    // Write then rename so a crash never leaves a truncated cache behind.
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, &bytes).with_context(|| format!("writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("renaming to {}", path.display()))?;
+    Ok(())
+}
+
+// Only documents whose file is unchanged since the cache was written are
+// returned, with their symbols and identifier occurrences.
+pub fn load(path: &Path) -> Result<(SymbolIndex, ReferenceIndex)> {
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+        bail!("{} is not a symbol cache", path.display());
+    }
+    let version = u32::from_le_bytes(bytes[MAGIC.len()..header].try_into()?);
+    if version != FORMAT_VERSION {
+        bail!("symbol cache version {version} is not supported");
+    }
+    let (file, _): (CacheFile, usize) =
+        bincode::serde::decode_from_slice(&bytes[header..], bincode::config::standard())?;
+
+    let index = SymbolIndex::default();
+    let references = ReferenceIndex::default();
+    for doc in file.docs {
+        let current = doc.uri.to_file_path().ok().and_then(|p| mtime_of(&p));
+        if current.is_none() || current != doc.mtime {
+            continue;
+        }
+        let entries = doc
+            .symbols
+            .into_iter()
+            .map(|s| {
+                SymbolEntry::new(s.name, s.uri, s.path, s.range, s.kind, s.container)
+                    .with_signature(s.signature)
+                    .with_supertype(s.supertype)
+            })
+            .collect();
+        index.insert_entries(&doc.uri, entries);
+        references.upsert_doc(&doc.uri, doc.occurrences);
+    }
+    Ok((index, references))
+}
+
+pub fn mtime_of(path: &Path) -> Option<(u64, u32)> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some((since.as_secs(), since.subsec_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("parsec-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn index_file(
+        index: &SymbolIndex,
+        references: &ReferenceIndex,
+        path: &Path,
+        name: &str,
+    ) -> Url {
+        fs::write(path, format!("{name}() = 1\n")).unwrap();
+        let uri = Url::from_file_path(path).unwrap();
+        let entry = SymbolEntry::new(
+            name.to_string(),
+            uri.clone(),
+            path.to_path_buf(),
+            Range::default(),
+            SymbolKind::FUNCTION,
+            None,
+        );
+        index.insert_entries(&uri, vec![entry]);
+        references.upsert_doc(&uri, vec![(name.to_string(), Range::default(), true)]);
+        uri
+    }
+
+    #[test]
+    fn save_writes_only_kept_documents() {
+        let dir = temp_dir("cache-keep");
+        let (index, references) = (SymbolIndex::default(), ReferenceIndex::default());
+        let saved = index_file(&index, &references, &dir.join("a.jl"), "alpha");
+        let open = index_file(&index, &references, &dir.join("b.jl"), "beta");
+
+        let path = dir.join(CACHE_FILE);
+        save(&index, &references, &path, |uri| {
+            (uri != open.as_str()).then(|| mtime_of(&dir.join("a.jl")))?
+        })
+        .unwrap();
+        let (index, references) = load(&path).unwrap();
+        assert_eq!(index.doc_uris(), vec![saved.to_string()]);
+        assert_eq!(references.doc_uris(), vec![saved.to_string()]);
+        assert_eq!(references.occurrences(saved.as_str())[0].0, "alpha");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_drops_files_changed_since_save() {
+        let dir = temp_dir("cache-mtime");
+        let (index, references) = (SymbolIndex::default(), ReferenceIndex::default());
+        let file = dir.join("a.jl");
+        index_file(&index, &references, &file, "alpha");
+
+        let path = dir.join(CACHE_FILE);
+        save(&index, &references, &path, |_| mtime_of(&file)).unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let (index, _) = load(&path).unwrap();
+        assert!(index.doc_uris().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_path_is_outside_the_workspace() {
+        let root = temp_dir("cache-root");
+        if let Some(path) = cache_path(&root) {
+            assert!(!path.starts_with(&root));
+            assert_eq!(cache_path(&root), Some(path));
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    // The file changed after it was read but before the cache was written:
+    // the time recorded at read time no longer matches, so load drops it.
+    #[test]
+    fn save_keeps_the_mtime_recorded_when_read() {
+        let dir = temp_dir("cache-read-mtime");
+        let (index, references) = (SymbolIndex::default(), ReferenceIndex::default());
+        let file = dir.join("a.jl");
+        index_file(&index, &references, &file, "alpha");
+        let read = mtime_of(&file);
+
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let path = dir.join(CACHE_FILE);
+        save(&index, &references, &path, |_| read).unwrap();
+        let (index, _) = load(&path).unwrap();
+        assert!(index.doc_uris().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
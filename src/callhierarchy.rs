@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tower_lsp::lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Position, Range,
+    SymbolKind, Url,
+};
+use tree_sitter::Node;
+
+use crate::index::SymbolEntry;
+use crate::line_index::LineIndex;
+use crate::state::ServerState;
+use crate::symbols::{
+    collect_named_descendants_by, find_callers, identifier_at_position, is_definition_head,
+    name_node, short_function_call,
+};
+
+// There is no type inference, so calls are matched to definitions purely by
+// the callee's identifier text.
+
+pub fn prepare(state: &ServerState, uri: &str, pos: Position) -> Vec<CallHierarchyItem> {
+    let Some((name, _)) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))
+    else {
+        return Vec::new();
+    };
+    state
+        .symbols
+        .search_exact(&name, &[])
+        .iter()
+        .filter(|e| is_callable(e.kind))
+        .filter_map(|e| item_for_entry(state, e))
+        .collect()
+}
+
+pub fn incoming(state: &ServerState, item: &CallHierarchyItem) -> Vec<CallHierarchyIncomingCall> {
+    // Only documents that mention the name at all are worth parsing.
+    let mut uris: Vec<Url> = state
+        .references
+        .find_references(&item.name, false)
+        .into_iter()
+        .map(|loc| loc.uri)
+        .collect();
+    uris.sort();
+    uris.dedup();
+
+    let mut out = Vec::new();
+    for uri in uris {
+        let Some(doc) = state.docs.get(uri.as_str()) else {
+            continue;
+        };
+        let ranges = find_callers(&item.name, &doc, &state.lang);
+        if ranges.is_empty() {
+            continue;
+        }
+        let (Some(tree), text) = doc.parsed_snapshot() else {
+            continue;
+        };
+        drop(doc);
+        let idx = LineIndex::new(&text);
+
+        // Calls are grouped under the function they appear in; top-level
+        // calls are attributed to the file itself.
+        let mut by_caller: HashMap<(usize, usize), (CallHierarchyItem, Vec<Range>)> =
+            HashMap::new();
+        for range in ranges {
+            let caller = idx
+                .to_byte_offset(range.start)
+                .and_then(|at| tree.root_node().descendant_for_byte_range(at, at))
+                .and_then(enclosing_function);
+            let key = caller.map_or((0, text.len()), |c| (c.start_byte(), c.end_byte()));
+            let entry = by_caller.entry(key).or_insert_with(|| {
+                let from = match caller {
+                    Some(c) => item_for_node(c, &text, &idx, &uri),
+                    None => None,
+                }
+                .unwrap_or_else(|| file_item(&uri, &idx, text.len()));
+                (from, Vec::new())
+            });
+            entry.1.push(range);
+        }
+        let mut calls: Vec<_> = by_caller.into_iter().collect();
+        calls.sort_by_key(|(key, _)| *key);
+        out.extend(
+            calls
+                .into_iter()
+                .map(|(_, (from, from_ranges))| CallHierarchyIncomingCall { from, from_ranges }),
+        );
+    }
+    out
+}
+
+pub fn outgoing(state: &ServerState, item: &CallHierarchyItem) -> Vec<CallHierarchyOutgoingCall> {
+    let Some(doc) = state.docs.get(item.uri.as_str()) else {
+        return Vec::new();
+    };
+    doc.parse_with_debounce(&state.lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    drop(doc);
+    let idx = LineIndex::new(&text);
+    let Some(start) = idx.to_byte_offset(item.range.start) else {
+        return Vec::new();
+    };
+    let Some(def) = collect_named_descendants_by(tree.root_node(), &|n: &Node| {
+        n.start_byte() == start && is_function_node(*n)
+    })
+    .into_iter()
+    .next() else {
+        return Vec::new();
+    };
+
+    let mut order: Vec<String> = Vec::new();
+    let mut ranges: HashMap<String, Vec<Range>> = HashMap::new();
+    for call in call_sites(def) {
+        let Some(callee) = callee_of(call) else {
+            continue;
+        };
+        let name = text[callee.byte_range()].to_string();
+        let range = idx.range_of(callee.start_byte(), callee.end_byte());
+        let slot = ranges.entry(name.clone()).or_default();
+        if slot.is_empty() {
+            order.push(name);
+        }
+        slot.push(range);
+    }
+
+    let mut out = Vec::new();
+    for name in order {
+        let from_ranges = ranges.remove(&name).unwrap_or_default();
+        let resolved = state
+            .symbols
+            .search_exact(&name, &[])
+            .iter()
+            .filter(|e| is_callable(e.kind))
+            .find_map(|e| item_for_entry(state, e));
+        // Callees outside the index (Base, packages not on disk) still show
+        // up, pointing at their first call site.
+        let to = resolved.unwrap_or_else(|| CallHierarchyItem {
+            name,
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: item.uri.clone(),
+            range: from_ranges[0],
+            selection_range: from_ranges[0],
+            data: None,
+        });
+        out.push(CallHierarchyOutgoingCall { to, from_ranges });
+    }
+    out
+}
+
+fn is_callable(kind: SymbolKind) -> bool {
+    matches!(
+        kind,
+        SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::INTERFACE
+    )
+}
+
+fn item_for_entry(state: &ServerState, entry: &SymbolEntry) -> Option<CallHierarchyItem> {
+    let doc = state.docs.get(entry.uri.as_str())?;
+    doc.parse_with_debounce(&state.lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    drop(doc);
+    let tree = tree?;
+    let idx = LineIndex::new(&text);
+    let start = idx.to_byte_offset(entry.range.start)?;
+    let def = collect_named_descendants_by(tree.root_node(), &|n: &Node| {
+        n.start_byte() == start && is_function_node(*n)
+    })
+    .into_iter()
+    .next()?;
+    let mut item = item_for_node(def, &text, &idx, &entry.uri)?;
+    item.detail = entry.container.as_deref().map(str::to_string);
+    Some(item)
+}
+
+fn item_for_node(def: Node, text: &str, idx: &LineIndex, uri: &Url) -> Option<CallHierarchyItem> {
+    let name = name_node(def)?;
+    Some(CallHierarchyItem {
+        name: text[name.byte_range()].to_string(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range: idx.range_of(def.start_byte(), def.end_byte()),
+        selection_range: idx.range_of(name.start_byte(), name.end_byte()),
+        data: None,
+    })
+}
+
+fn file_item(uri: &Url, idx: &LineIndex, len: usize) -> CallHierarchyItem {
+    let name = uri
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .unwrap_or(uri.as_str())
+        .to_string();
+    let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+    CallHierarchyItem {
+        name,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range: idx.range_of(0, len),
+        selection_range: start,
+        data: None,
+    }
+}
+
+// function/macro definitions, plus the short form `f(x) = ...`.
+fn is_function_node(node: Node) -> bool {
+    match node.kind() {
+        "function_definition" | "macro_definition" => true,
+        "assignment" => short_function_call(node).is_some(),
+        _ => false,
+    }
+}
+
+fn enclosing_function(node: Node) -> Option<Node> {
+    let mut cur = node.parent();
+    while let Some(n) = cur {
+        if is_function_node(n) {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    None
+}
+
+// Call expressions that are real calls, skipping the `f(x)` heads of
+// definitions.
+fn call_sites(root: Node) -> Vec<Node> {
+    collect_named_descendants_by(root, &|n: &Node| {
+        matches!(n.kind(), "call_expression" | "broadcast_call_expression")
+            && !is_definition_head(*n)
+    })
+}
+
+fn callee_of(call: Node) -> Option<Node> {
+    let callee = call.named_child(0)?;
+    match callee.kind() {
+        "identifier" => Some(callee),
+        "field_expression" => {
+            let last = callee.named_child(callee.named_child_count().checked_sub(1)?)?;
+            (last.kind() == "identifier").then_some(last)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Range, TextEdit, Url, WorkspaceEdit,
+};
+use tree_sitter::{Language, Node};
+
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+
+pub fn collect(
+    doc: &DocState,
+    lang: &Language,
+    uri: &Url,
+    range: Range,
+    only: Option<&[CodeActionKind]>,
+) -> Vec<CodeActionOrCommand> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let Some(offset) = idx.to_byte_offset(range.start) else {
+        return Vec::new();
+    };
+    let Some(at) = tree.root_node().descendant_for_byte_range(offset, offset) else {
+        return Vec::new();
+    };
+
+    let mut out = Vec::new();
+    if let Some(func) = enclosing(at, "function_definition") {
+        if func.parent().is_some_and(|p| p.kind() == "source_file") {
+            out.push(wrap_in_module(&text, &idx, uri, func));
+        }
+        if let Some(action) = convert_to_short_function(&text, &idx, uri, func) {
+            out.push(action);
+        }
+    }
+    if let Some(action) = add_type_annotation(&text, &idx, uri, at) {
+        out.push(action);
+    }
+    if let Some(only) = only {
+        out.retain(|action| match action {
+            CodeActionOrCommand::CodeAction(a) => {
+                a.kind.as_ref().is_some_and(|k| requested(k, only))
+            }
+            CodeActionOrCommand::Command(_) => false,
+        });
+    }
+    out
+}
+
+// Kinds are hierarchical: asking for `refactor` also asks for
+// `refactor.extract`, but not for `refactorx`.
+fn requested(kind: &CodeActionKind, only: &[CodeActionKind]) -> bool {
+    let kind = kind.as_str();
+    only.iter().any(|o| {
+        kind.strip_prefix(o.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+fn enclosing<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut cur = Some(node);
+    while let Some(n) = cur {
+        if n.kind() == kind {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    None
+}
+
+fn make_action(
+    title: &str,
+    kind: CodeActionKind,
+    uri: &Url,
+    edits: Vec<TextEdit>,
+) -> CodeActionOrCommand {
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.into(),
+        kind: Some(kind),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+            change_annotations: None,
+        }),
+        ..Default::default()
+    })
+}
+
+fn wrap_in_module(text: &str, idx: &LineIndex, uri: &Url, func: Node) -> CodeActionOrCommand {
+    let start = idx.to_pos(func.start_byte());
+    let end = idx.to_pos(func.end_byte());
+    let body = &text[func.start_byte()..func.end_byte()];
+    let indented: Vec<String> = body
+        .lines()
+        .map(|l| {
+            if l.is_empty() {
+                String::new()
+            } else {
+                format!("    {l}")
+            }
+        })
+        .collect();
+    let new_text = format!("module NewModule\n\n{}\n\nend", indented.join("\n"));
+    make_action(
+        "Wrap in module",
+        CodeActionKind::REFACTOR_EXTRACT,
+        uri,
+        vec![TextEdit {
+            range: Range { start, end },
+            new_text,
+        }],
+    )
+}
+
+fn convert_to_short_function(
+    text: &str,
+    idx: &LineIndex,
+    uri: &Url,
+    func: Node,
+) -> Option<CodeActionOrCommand> {
+    let mut signature = None;
+    let mut body = Vec::new();
+    for i in 0..func.named_child_count() {
+        let ch = func.named_child(i)?;
+        match ch.kind() {
+            "signature" => signature = Some(ch),
+            "line_comment" | "block_comment" => return None,
+            _ => body.push(ch),
+        }
+    }
+    let signature = signature?;
+    let [ret] = body.as_slice() else {
+        return None;
+    };
+    if ret.kind() != "return_statement" {
+        return None;
+    }
+    let expr = ret.named_child(0)?;
+    let new_text = format!(
+        "{} = {}",
+        &text[signature.start_byte()..signature.end_byte()],
+        &text[expr.start_byte()..expr.end_byte()]
+    );
+    Some(make_action(
+        "Convert to short-function definition",
+        CodeActionKind::REFACTOR_REWRITE,
+        uri,
+        vec![TextEdit {
+            range: idx.range_of(func.start_byte(), func.end_byte()),
+            new_text,
+        }],
+    ))
+}
+
+fn add_type_annotation(
+    text: &str,
+    idx: &LineIndex,
+    uri: &Url,
+    at: Node,
+) -> Option<CodeActionOrCommand> {
+    if at.kind() != "identifier" {
+        return None;
+    }
+    let args = at.parent().filter(|p| p.kind() == "argument_list")?;
+    let call = args.parent().filter(|p| p.kind() == "call_expression")?;
+    if call.parent().is_none_or(|p| p.kind() != "signature") {
+        return None;
+    }
+    let name = &text[at.start_byte()..at.end_byte()];
+    let end = idx.to_pos(at.end_byte());
+    Some(make_action(
+        &format!("Add type annotation to `{name}`"),
+        CodeActionKind::REFACTOR_REWRITE,
+        uri,
+        vec![TextEdit {
+            range: Range { start: end, end },
+            new_text: "::Any".into(),
+        }],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ServerState;
+    use tower_lsp::lsp_types::Position;
+
+    fn titles(only: Option<&[CodeActionKind]>) -> Vec<String> {
+        let state = ServerState::default();
+        let uri = Url::parse("file:///t.jl").unwrap();
+        state.open_doc(
+            uri.to_string(),
+            "function f(x)\n    return x + 1\nend\n".into(),
+        );
+        let doc = state.docs.get(uri.as_str()).unwrap();
+        let at = Position::new(1, 11);
+        collect(&doc, &state.lang, &uri, Range::new(at, at), only)
+            .into_iter()
+            .filter_map(|a| match a {
+                CodeActionOrCommand::CodeAction(a) => Some(a.title),
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_filters_by_kind_prefix() {
+        let all = titles(None);
+        assert!(all.len() >= 2, "{all:?}");
+        let extract = titles(Some(&[CodeActionKind::REFACTOR_EXTRACT]));
+        assert_eq!(extract.len(), 1, "{extract:?}");
+        assert_eq!(titles(Some(&[CodeActionKind::REFACTOR])), all);
+        assert!(titles(Some(&[CodeActionKind::QUICKFIX])).is_empty());
+        assert!(!requested(
+            &CodeActionKind::new("refactorx"),
+            &[CodeActionKind::REFACTOR]
+        ));
+    }
+}
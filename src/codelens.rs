@@ -0,0 +1,93 @@
+use serde_json::json;
+use std::time::Duration;
+use tower_lsp::lsp_types::{CodeLens, Command, Url};
+use tree_sitter::{Language, Node};
+
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+use crate::symbols::collect_named_descendants_by;
+
+pub const RUN_TESTSET_COMMAND: &str = "parsec.runTestset";
+
+pub fn collect(doc: &DocState, lang: &Language, uri: &Url) -> Vec<CodeLens> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let macros = collect_named_descendants_by(tree.root_node(), &|n: &Node| {
+        n.kind() == "macrocall_expression"
+    });
+
+    let testsets: Vec<Node> = macros
+        .iter()
+        .copied()
+        .filter(|&m| macro_name(m, &text) == Some("@testset"))
+        .collect();
+    let mut out = Vec::new();
+    for testset in testsets {
+        let range = idx.range_of(testset.start_byte(), testset.end_byte());
+        // The lens sits on the `@testset` line only.
+        let mut lens_range = range;
+        lens_range.end = idx.to_pos(idx.line_end(range.start.line as usize));
+        out.push(CodeLens {
+            range: lens_range,
+            command: Some(Command {
+                title: "▷ Run testset".to_string(),
+                command: RUN_TESTSET_COMMAND.to_string(),
+                arguments: Some(vec![json!(uri), json!(range)]),
+            }),
+            data: None,
+        });
+
+        // Assertions count towards the innermost testset around them.
+        let tests = macros
+            .iter()
+            .filter(|&&m| {
+                macro_name(m, &text).is_some_and(is_test_assertion)
+                    && enclosing_testset(m, &text).is_some_and(|t| t.id() == testset.id())
+            })
+            .count();
+        if tests > 0 {
+            let title = if tests == 1 {
+                "1 test".to_string()
+            } else {
+                format!("{tests} tests")
+            };
+            out.push(CodeLens {
+                range: lens_range,
+                command: Some(Command {
+                    title,
+                    command: String::new(),
+                    arguments: None,
+                }),
+                data: None,
+            });
+        }
+    }
+    out
+}
+
+// `@testset` and `Test.@testset` both name the macro `@testset`.
+fn macro_name<'a>(call: Node, text: &'a str) -> Option<&'a str> {
+    let mut cursor = call.walk();
+    let ident = call
+        .named_children(&mut cursor)
+        .find(|c| c.kind() == "macro_identifier")?;
+    Some(&text[ident.byte_range()])
+}
+
+fn is_test_assertion(name: &str) -> bool {
+    name == "@test" || name.starts_with("@test_")
+}
+
+fn enclosing_testset<'a>(node: Node<'a>, text: &str) -> Option<Node<'a>> {
+    let mut cur = node.parent();
+    while let Some(n) = cur {
+        if n.kind() == "macrocall_expression" && macro_name(n, text) == Some("@testset") {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    None
+}
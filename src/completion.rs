@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Position, SymbolKind};
+
+use crate::index::SymbolFilter;
+use crate::line_index::LineIndex;
+use crate::state::{DocState, ServerState};
+use crate::symbols::{find_enclosing_module, scope_name_node};
+
+pub fn complete(
+    state: &ServerState,
+    uri: &str,
+    pos: Position,
+    limit: usize,
+) -> Vec<CompletionItem> {
+    let Some(doc) = state.docs.get(uri) else {
+        return Vec::new();
+    };
+    let text = doc.text();
+    let idx = LineIndex::new(&text);
+    let Some(offset) = idx.to_byte_offset(pos) else {
+        return Vec::new();
+    };
+    let before = &text[idx.line_start(pos.line as usize)..offset];
+    let (module, prefix) = split_member_access(before);
+    if prefix.is_empty() && module.is_none() {
+        return Vec::new();
+    }
+    let local_module = enclosing_module_name(&doc, &state.lang, offset);
+    drop(doc);
+
+    // After a typed `@` the client keeps it, so macro names go in bare.
+    let after_at = before[..before.len() - prefix.len()].ends_with('@');
+    let active = state.active_doc();
+    // `Mod.` only offers what the index saw defined inside `Mod`.
+    let filter = SymbolFilter {
+        container: module,
+        ..SymbolFilter::default()
+    };
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for sym in state
+        .symbols
+        .search_with_filter(prefix, &filter, active.as_deref(), limit)
+    {
+        // Methods of one function share a name; offer it once.
+        if !seen.insert(sym.name.clone()) {
+            continue;
+        }
+        // Names defined in the module around the cursor sort first; the
+        // client still orders within each group by label.
+        let local = local_module.is_some() && sym.container.as_deref() == local_module.as_deref();
+        out.push(CompletionItem {
+            sort_text: Some(format!("{}{}", if local { 0 } else { 1 }, sym.name)),
+            label: sym.name.to_string(),
+            kind: Some(completion_kind(sym.kind)),
+            detail: sym.container.as_deref().map(str::to_string),
+            insert_text: Some(match sym.name.strip_prefix('@') {
+                Some(bare) if after_at => bare.to_string(),
+                _ => sym.name.to_string(),
+            }),
+            ..Default::default()
+        });
+    }
+    out
+}
+
+fn enclosing_module_name(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    offset: usize,
+) -> Option<String> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return None;
+    };
+    let module = find_enclosing_module(tree.root_node(), offset)?;
+    let name = scope_name_node(module)?;
+    Some(text[name.byte_range()].to_string())
+}
+
+// Splits the text before the cursor into an optional `Module.` qualifier and
+// the partial identifier being typed.
+fn split_member_access(before: &str) -> (Option<&str>, &str) {
+    let prefix_start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident_char(c))
+        .last()
+        .map_or(before.len(), |(i, _)| i);
+    let prefix = &before[prefix_start..];
+    let Some(head) = before[..prefix_start].strip_suffix('.') else {
+        return (None, prefix);
+    };
+    let module_start = head
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident_char(c))
+        .last()
+        .map_or(head.len(), |(i, _)| i);
+    let module = &head[module_start..];
+    ((!module.is_empty()).then_some(module), prefix)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '!'
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::FUNCTION | SymbolKind::METHOD => CompletionItemKind::FUNCTION,
+        SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+        SymbolKind::CLASS => CompletionItemKind::CLASS,
+        SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+        SymbolKind::MODULE | SymbolKind::NAMESPACE => CompletionItemKind::MODULE,
+        SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
+        SymbolKind::ENUM => CompletionItemKind::ENUM,
+        SymbolKind::ENUM_MEMBER => CompletionItemKind::ENUM_MEMBER,
+        SymbolKind::FIELD => CompletionItemKind::FIELD,
+        SymbolKind::TYPE_PARAMETER => CompletionItemKind::TYPE_PARAMETER,
+        _ => CompletionItemKind::VARIABLE,
+    }
+}
@@ -0,0 +1,142 @@
+use serde_json::Value;
+
+// Settings that can change while the server runs. They arrive through
+// initializationOptions and workspace/didChangeConfiguration in the same
+// shape, optionally wrapped in a top-level "parsec" section:
+//
+//   parse.debounceMs        delay before re-parsing after an edit
+//   parse.maxTrees          parse trees kept in memory; the least recently
+//                           used beyond this are dropped and rebuilt on
+//                           demand. 0 keeps them all
+//   workspace.symbolLimit   most results a workspace/symbol request returns;
+//                           0 means the default. Ranking keeps a heap of
+//                           this size and every result is serialized, so
+//                           large limits mostly cost response size and
+//                           client-side filtering time
+//   index.excludePatterns   gitignore-style globs the indexer skips
+//   index.maxThreads        indexer worker threads; 0 uses one per core
+//   logLevel                tracing filter, e.g. "info" or "parsec=debug"
+//   inlayHints.enabled      whether textDocument/inlayHint returns anything
+//   diagnostics.duplicateMethods
+//                           whether a method defined twice with the same
+//                           signature in one file gets a warning
+//
+// A change only needs to carry the keys it sets; the rest keep their
+// current values.
+//
+// These are read from initializationOptions only, never wrapped in
+// "parsec", and later changes to them are ignored until the server is
+// restarted:
+//
+//   boostActiveDoc          workspace/symbol ranks the active document first
+//   watchedFilesCoalesceMs  window in which file watcher events are batched
+//   fileExtensions          extensions the indexer and watcher pick up
+//   index.maxFileSizeBytes  files larger than this are not indexed
+// Enough to fill an editor's symbol picker while keeping each keystroke's
+// response small.
+pub const DEFAULT_WORKSPACE_SYMBOL_LIMIT: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsecConfig {
+    pub debounce_ms: u64,
+    pub max_parse_trees: usize,
+    pub max_workspace_symbols: usize,
+    pub indexing_exclude_patterns: Vec<String>,
+    pub max_indexing_threads: usize,
+    pub log_level: String,
+    pub inlay_hints_enabled: bool,
+    pub duplicate_methods_enabled: bool,
+}
+
+impl Default for ParsecConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 120,
+            max_parse_trees: 200,
+            max_workspace_symbols: DEFAULT_WORKSPACE_SYMBOL_LIMIT,
+            indexing_exclude_patterns: Vec::new(),
+            max_indexing_threads: 0,
+            log_level: "info".to_string(),
+            inlay_hints_enabled: false,
+            duplicate_methods_enabled: true,
+        }
+    }
+}
+
+impl ParsecConfig {
+    // Keys that are absent or of the wrong type keep their value in `self`.
+    pub fn merge_lsp_value(&self, v: &Value) -> Self {
+        let mut config = self.clone();
+        let v = v.get("parsec").unwrap_or(v);
+        if let Some(ms) = v.pointer("/parse/debounceMs").and_then(Value::as_u64) {
+            config.debounce_ms = ms;
+        }
+        if let Some(trees) = v.pointer("/parse/maxTrees").and_then(Value::as_u64) {
+            config.max_parse_trees = trees as usize;
+        }
+        if let Some(limit) = v
+            .pointer("/workspace/symbolLimit")
+            .and_then(Value::as_u64)
+            .filter(|&l| l > 0)
+        {
+            config.max_workspace_symbols = limit as usize;
+        }
+        if let Some(patterns) = v
+            .pointer("/index/excludePatterns")
+            .and_then(Value::as_array)
+        {
+            config.indexing_exclude_patterns = patterns
+                .iter()
+                .filter_map(|p| p.as_str().map(str::to_string))
+                .collect();
+        }
+        if let Some(threads) = v.pointer("/index/maxThreads").and_then(Value::as_u64) {
+            config.max_indexing_threads = threads as usize;
+        }
+        if let Some(level) = v.get("logLevel").and_then(Value::as_str) {
+            config.log_level = level.to_string();
+        }
+        if let Some(enabled) = v.pointer("/inlayHints/enabled").and_then(Value::as_bool) {
+            config.inlay_hints_enabled = enabled;
+        }
+        if let Some(enabled) = v
+            .pointer("/diagnostics/duplicateMethods")
+            .and_then(Value::as_bool)
+        {
+            config.duplicate_methods_enabled = enabled;
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_keeps_keys_the_change_leaves_out() {
+        let config = ParsecConfig::default().merge_lsp_value(&json!({
+            "parse": { "debounceMs": 40 },
+            "inlayHints": { "enabled": true },
+        }));
+        let config = config.merge_lsp_value(&json!({ "parsec": { "logLevel": "debug" } }));
+        assert_eq!(config.debounce_ms, 40);
+        assert!(config.inlay_hints_enabled);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(
+            config.max_parse_trees,
+            ParsecConfig::default().max_parse_trees
+        );
+    }
+
+    #[test]
+    fn merge_ignores_values_of_the_wrong_type() {
+        let base = ParsecConfig {
+            debounce_ms: 40,
+            ..ParsecConfig::default()
+        };
+        let config = base.merge_lsp_value(&json!({ "parse": { "debounceMs": "fast" } }));
+        assert_eq!(config, base);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use tower_lsp::lsp_types::{LocationLink, Position, Range, SymbolKind};
+
+use crate::index::SymbolEntry;
+use crate::line_index::LineIndex;
+use crate::state::ServerState;
+use crate::symbols::{definition_node_at, identifier_at_position, name_node};
+
+// Like hover, definitions are found by name in the workspace index, so every
+// indexed symbol with that name is a candidate. When there is none, names
+// differing only in case are offered instead, for a miscapitalized name.
+pub fn goto(state: &ServerState, uri: &str, pos: Position) -> Vec<LocationLink> {
+    let Some((name, origin)) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))
+    else {
+        return Vec::new();
+    };
+    let mut entries = state.symbols.search_exact(&name, &[]);
+    entries.extend(state.symbols.search_exact(&format!("@{name}"), &[]));
+    if entries.is_empty() {
+        entries = state.symbols.search_exact_case_insensitive(&name, &[]);
+    }
+    entries
+        .iter()
+        .map(|entry| LocationLink {
+            origin_selection_range: Some(origin),
+            target_uri: entry.uri.clone(),
+            target_range: entry.range,
+            target_selection_range: name_range(state, entry).unwrap_or(entry.range),
+        })
+        .collect()
+}
+
+// For a type, the concrete structs below it, found through declared
+// supertypes at any depth; for a function, all of its methods. Like goto,
+// matching is by name only.
+pub fn implementations(state: &ServerState, uri: &str, pos: Position) -> Vec<LocationLink> {
+    let Some((name, origin)) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))
+    else {
+        return Vec::new();
+    };
+    let named = state.symbols.search_exact(&name, &[]);
+    let is_type = |e: &SymbolEntry| matches!(e.kind, SymbolKind::STRUCT | SymbolKind::CLASS);
+    let entries: Vec<SymbolEntry> = if named.iter().any(is_type) {
+        let mut seen = HashSet::from([name.clone()]);
+        let mut pending = vec![name];
+        let mut concrete = Vec::new();
+        while let Some(sup) = pending.pop() {
+            for sub in state.symbols.subtypes_of(&sup) {
+                if !is_type(&sub) {
+                    continue;
+                }
+                if seen.insert(sub.name.to_string()) {
+                    pending.push(sub.name.to_string());
+                }
+                if !is_abstract(state, &sub) {
+                    concrete.push(sub);
+                }
+            }
+        }
+        concrete
+    } else {
+        named
+            .into_iter()
+            .filter(|e| e.kind == SymbolKind::FUNCTION)
+            .collect()
+    };
+    entries
+        .iter()
+        .map(|entry| LocationLink {
+            origin_selection_range: Some(origin),
+            target_uri: entry.uri.clone(),
+            target_range: entry.range,
+            target_selection_range: name_range(state, entry).unwrap_or(entry.range),
+        })
+        .collect()
+}
+
+// Abstract types and mutable structs share the CLASS kind in the index, so
+// the definition itself tells them apart.
+fn is_abstract(state: &ServerState, entry: &SymbolEntry) -> bool {
+    if entry.kind != SymbolKind::CLASS {
+        return false;
+    }
+    let Some(doc) = state.docs.get(entry.uri.as_str()) else {
+        return false;
+    };
+    doc.parse_with_debounce(&state.lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    drop(doc);
+    let Some(tree) = tree else {
+        return false;
+    };
+    let idx = LineIndex::new(&text);
+    idx.to_byte_offset(entry.range.start)
+        .and_then(|at| definition_node_at(tree.root_node(), at))
+        .is_some_and(|def| def.kind() == "abstract_definition")
+}
+
+// The index stores the whole definition; the name inside it comes from the
+// target's tree. Symbols synthesized from macros have no definition node and
+// already point at their name.
+pub(crate) fn name_range(state: &ServerState, entry: &SymbolEntry) -> Option<Range> {
+    let doc = state.docs.get(entry.uri.as_str())?;
+    doc.parse_with_debounce(&state.lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    drop(doc);
+    let tree = tree?;
+    let idx = LineIndex::new(&text);
+    let def = definition_node_at(tree.root_node(), idx.to_byte_offset(entry.range.start)?)?;
+    let name = name_node(def)?;
+    Some(idx.range_of(name.start_byte(), name.end_byte()))
+}
@@ -1,12 +1,58 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
 use tower_lsp::lsp_types::{
-    Diagnostic, DiagnosticSeverity, Position, Range,
+    CodeDescription, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
+    NumberOrString, Position, Range, Url,
 };
+use tree_sitter::{Language, Node, Tree};
 
-pub fn simple_syntax_error_diag(
-    msg: &str,
-    line: usize,
-    col: usize,
-) -> Diagnostic {
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+use crate::symbols::{kind_for_node, name_node, short_function_call};
+
+// Diagnostic codes. The numbers are part of the protocol surface (editors and
+// tests match on them), so never renumber an existing code.
+//
+//   E001  SyntaxError      the parser could not make sense of a region
+//   E002  MissingToken     the parser inserted a token that is not there,
+//                          typically a missing `end` or closing bracket
+//   E003  UnexpectedToken  a single stray token the parser had to skip
+//   E004  DuplicateMethod  a method defined again in the same file with the
+//                          same signature, replacing the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    SyntaxError(u32),
+    MissingToken(u32),
+    UnexpectedToken(u32),
+    DuplicateMethod(u32),
+}
+
+impl DiagnosticCode {
+    pub const SYNTAX_ERROR: Self = Self::SyntaxError(1);
+    pub const MISSING_TOKEN: Self = Self::MissingToken(2);
+    pub const UNEXPECTED_TOKEN: Self = Self::UnexpectedToken(3);
+    pub const DUPLICATE_METHOD: Self = Self::DuplicateMethod(4);
+
+    pub fn number(self) -> u32 {
+        match self {
+            Self::SyntaxError(n)
+            | Self::MissingToken(n)
+            | Self::UnexpectedToken(n)
+            | Self::DuplicateMethod(n) => n,
+        }
+    }
+
+    fn description(self) -> Option<CodeDescription> {
+        let href = Url::parse(DIAGNOSTIC_CODES_URL).ok()?;
+        Some(CodeDescription { href })
+    }
+}
+
+const DIAGNOSTIC_CODES_URL: &str = "https://github.com/pseudofractal/Parsec#diagnostic-codes";
+
+pub fn simple_syntax_error_diag(msg: &str, line: usize, col: usize) -> Diagnostic {
     Diagnostic {
         range: Range {
             start: Position {
@@ -29,3 +75,379 @@ pub fn simple_syntax_error_diag(
     }
 }
 
+const SNIPPET_MAX_CHARS: usize = 40;
+
+// Tokens that open a block closed by `end`.
+const BLOCK_OPENERS: &[&str] = &[
+    "function",
+    "macro",
+    "module",
+    "baremodule",
+    "struct",
+    "mutable",
+    "abstract",
+    "primitive",
+    "if",
+    "for",
+    "while",
+    "let",
+    "try",
+    "begin",
+    "quote",
+    "do",
+];
+
+// Nodes whose direct children are statements. A bare `end` there closes
+// nothing; the grammar accepts it as an identifier, so it never becomes an
+// ERROR on its own.
+const STATEMENT_PARENTS: &[&str] = &[
+    "source_file",
+    "module_definition",
+    "function_definition",
+    "macro_definition",
+    "struct_definition",
+    "if_statement",
+    "elseif_clause",
+    "else_clause",
+    "for_statement",
+    "while_statement",
+    "let_statement",
+    "try_statement",
+    "catch_clause",
+    "finally_clause",
+    "compound_statement",
+    "quote_statement",
+    "do_clause",
+];
+
+// Diagnostics for a pull request, together with the result id describing the
+// text they were computed from. None means `previous` is still current.
+pub fn pull(
+    doc: &DocState,
+    lang: &Language,
+    previous: Option<&str>,
+    duplicate_methods: bool,
+) -> (String, Option<Vec<Diagnostic>>) {
+    let id = result_id(doc.version(), &doc.text());
+    if previous == Some(id.as_str()) {
+        return (id, None);
+    }
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    let diags = match tree {
+        Some(tree) => {
+            let mut diags = diagnostics_from_tree(&tree, &text);
+            if duplicate_methods && let Ok(uri) = Url::parse(doc.uri()) {
+                diags.extend(duplicate_method_diagnostics(&tree, &text, &uri));
+            }
+            diags
+        }
+        None => vec![simple_syntax_error_diag("parse error", 0, 0)],
+    };
+    (result_id(doc.version(), &text), Some(diags))
+}
+
+// Files the indexer read from disk all sit at version 0, so the version
+// alone cannot tell their revisions apart.
+fn result_id(version: i32, text: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    format!("{version}-{:016x}", hasher.finish())
+}
+
+// One diagnostic per ERROR node (outermost only) and per MISSING node the
+// parser inserted while recovering, plus stray `end`s.
+pub fn diagnostics_from_tree(tree: &Tree, text: &str) -> Vec<Diagnostic> {
+    let root = tree.root_node();
+    let idx = LineIndex::new(text);
+    let mut out = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.is_error()
+            && let Some((keyword, message)) = unclosed_block(node, text)
+        {
+            out.push(node_diag(
+                &idx,
+                keyword,
+                DiagnosticSeverity::ERROR,
+                DiagnosticCode::MISSING_TOKEN,
+                message,
+            ));
+            continue;
+        }
+        if node.is_error() {
+            let snippet = snippet_of(&text[node.byte_range()]);
+            let message = if snippet.is_empty() {
+                "syntax error".to_string()
+            } else {
+                format!("syntax error near `{snippet}`")
+            };
+            // An ERROR wrapping nothing but a token is a stray token rather
+            // than a malformed construct.
+            let code = if node.named_child_count() == 0 {
+                DiagnosticCode::UNEXPECTED_TOKEN
+            } else {
+                DiagnosticCode::SYNTAX_ERROR
+            };
+            out.push(node_diag(
+                &idx,
+                node,
+                DiagnosticSeverity::ERROR,
+                code,
+                message,
+            ));
+            continue;
+        }
+        if node.is_missing() {
+            let message = match node.parent().filter(|_| node.kind() == "end") {
+                Some(block) => missing_end_message(block, text),
+                None => format!("missing `{}`", node.kind()),
+            };
+            out.push(node_diag(
+                &idx,
+                node,
+                DiagnosticSeverity::WARNING,
+                DiagnosticCode::MISSING_TOKEN,
+                message,
+            ));
+            continue;
+        }
+        if is_stray_end(node, text) {
+            out.push(node_diag(
+                &idx,
+                node,
+                DiagnosticSeverity::ERROR,
+                DiagnosticCode::UNEXPECTED_TOKEN,
+                "unexpected `end`: there is no open block to close".to_string(),
+            ));
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
+
+// Methods at the top level of a file or module that repeat an earlier
+// method's name and positional parameter types. Definitions nested in
+// functions or `if` blocks are skipped, since picking one of several is
+// usually the point there. Comparison is textual: `x::Int` and `x::Int64`
+// count as different, as do default values that expand to the same method.
+pub fn duplicate_method_diagnostics(tree: &Tree, text: &str, uri: &Url) -> Vec<Diagnostic> {
+    let idx = LineIndex::new(text);
+    let mut seen: HashMap<(usize, String), Node> = HashMap::new();
+    let mut out = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if kind_for_node(node).is_some()
+            && let Some(key) = method_key(node, text)
+            && let Some(scope) = method_scope(node)
+        {
+            match seen.get(&(scope, key.clone())) {
+                Some(first) => {
+                    let mut diag = node_diag(
+                        &idx,
+                        name_node(node).unwrap_or(node),
+                        DiagnosticSeverity::WARNING,
+                        DiagnosticCode::DUPLICATE_METHOD,
+                        format!(
+                            "method `{key}` is already defined at line {}",
+                            first.start_position().row + 1
+                        ),
+                    );
+                    diag.related_information = Some(vec![DiagnosticRelatedInformation {
+                        location: Location {
+                            uri: uri.clone(),
+                            range: idx.range_of(first.start_byte(), first.end_byte()),
+                        },
+                        message: "first definition".to_string(),
+                    }]);
+                    out.push(diag);
+                }
+                None => {
+                    seen.insert((scope, key), node);
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
+
+// `name(T1, T2...)` plus any `where` clause, with untyped parameters as
+// `Any` and keyword arguments left out since they take no part in dispatch.
+fn method_key(def: Node, text: &str) -> Option<String> {
+    let mut head = match def.kind() {
+        "function_definition" => {
+            let mut cursor = def.walk();
+            def.named_children(&mut cursor)
+                .find(|n| n.kind() == "signature")?
+                .named_child(0)?
+        }
+        "assignment" => short_function_call(def).and(def.named_child(0))?,
+        _ => return None,
+    };
+    let mut clauses = Vec::new();
+    loop {
+        match head.kind() {
+            "where_expression" => {
+                let count = head.named_child_count();
+                clauses.extend((1..count).filter_map(|i| head.named_child(i)));
+                head = head.named_child(0)?;
+            }
+            "typed_expression" => head = head.named_child(0)?,
+            "call_expression" => break,
+            _ => return None,
+        }
+    }
+    let callee = head.named_child(0)?;
+    let mut cursor = head.walk();
+    let args = head
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "argument_list")?;
+    let mut params = Vec::new();
+    let mut cursor = args.walk();
+    for arg in args.children(&mut cursor) {
+        if arg.kind() == ";" {
+            break;
+        }
+        if arg.is_named() {
+            params.push(parameter_type(arg, text));
+        }
+    }
+    let mut key = format!("{}({})", &text[callee.byte_range()], params.join(", "));
+    for clause in clauses {
+        key.push_str(" where ");
+        key.push_str(&compact(&text[clause.byte_range()]));
+    }
+    Some(key)
+}
+
+fn parameter_type(param: Node, text: &str) -> String {
+    match param.kind() {
+        "typed_expression" => param
+            .named_child(param.named_child_count().saturating_sub(1))
+            .map_or_else(|| "Any".to_string(), |t| compact(&text[t.byte_range()])),
+        "unary_typed_expression" => param
+            .named_child(0)
+            .map_or_else(|| "Any".to_string(), |t| compact(&text[t.byte_range()])),
+        "named_argument" => param
+            .named_child(0)
+            .map_or_else(|| "Any".to_string(), |p| parameter_type(p, text)),
+        "splat_expression" => match param.named_child(0) {
+            Some(p) => format!("{}...", parameter_type(p, text)),
+            None => "Any...".to_string(),
+        },
+        _ => "Any".to_string(),
+    }
+}
+
+fn compact(text: &str) -> String {
+    text.split_whitespace().collect()
+}
+
+// The file or module a definition sits directly in, looking through macros
+// such as `@inline`; None for anything nested deeper.
+fn method_scope(def: Node) -> Option<usize> {
+    let mut parent = def.parent()?;
+    while matches!(
+        parent.kind(),
+        "macro_argument_list" | "macrocall_expression"
+    ) {
+        parent = parent.parent()?;
+    }
+    matches!(
+        parent.kind(),
+        "source_file" | "module_definition" | "bare_module_definition"
+    )
+    .then(|| parent.id())
+}
+
+// An ERROR that starts with a block keyword and holds no `end` of its own is
+// a block the parser ran off the end of the file looking to close.
+fn unclosed_block<'a>(error: Node<'a>, text: &str) -> Option<(Node<'a>, String)> {
+    let keyword = error
+        .child(0)
+        .filter(|k| BLOCK_OPENERS.contains(&k.kind()))?;
+    let mut cursor = error.walk();
+    if error.children(&mut cursor).any(|c| c.kind() == "end") {
+        return None;
+    }
+    Some((keyword, missing_end_message(error, text)))
+}
+
+fn missing_end_message(block: Node, text: &str) -> String {
+    let Some(keyword) = block.child(0) else {
+        return "missing `end`".to_string();
+    };
+    let mut opener = &text[keyword.byte_range()];
+    if opener == "mutable" {
+        opener = "mutable struct";
+    }
+    format!(
+        "missing `end` to close `{opener}` opened at line {}",
+        keyword.start_position().row + 1
+    )
+}
+
+fn is_stray_end(node: Node, text: &str) -> bool {
+    node.kind() == "identifier"
+        && &text[node.byte_range()] == "end"
+        && node
+            .parent()
+            .is_some_and(|p| STATEMENT_PARENTS.contains(&p.kind()))
+}
+
+fn node_diag(
+    idx: &LineIndex,
+    node: Node,
+    severity: DiagnosticSeverity,
+    code: DiagnosticCode,
+    message: String,
+) -> Diagnostic {
+    let mut diag = simple_syntax_error_diag(&message, 0, 0);
+    diag.range = idx.range_of(node.start_byte(), node.end_byte());
+    diag.severity = Some(severity);
+    diag.code = Some(NumberOrString::Number(code.number() as i32));
+    diag.code_description = code.description();
+    diag
+}
+
+fn snippet_of(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or("");
+    match line.char_indices().nth(SNIPPET_MAX_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn unclosed_function_reports_missing_end() {
+        let text = include_str!("../tests/fixtures/missing_end.jl");
+        let tree = parse::parse(text, None).unwrap();
+        let diags = diagnostics_from_tree(&tree, text);
+        assert_eq!(diags.len(), 1, "{diags:?}");
+        assert_eq!(
+            diags[0].message,
+            "missing `end` to close `function` opened at line 3"
+        );
+        assert_eq!(diags[0].range.start, Position::new(2, 0));
+    }
+
+    #[test]
+    fn stray_end_is_unexpected() {
+        let text = "f(x) = x\nend\n";
+        let tree = parse::parse(text, None).unwrap();
+        let diags = diagnostics_from_tree(&tree, text);
+        assert_eq!(diags.len(), 1, "{diags:?}");
+        assert_eq!(diags[0].range.start, Position::new(1, 0));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+use tower_lsp::lsp_types::{FormattingOptions, Position, Range, TextEdit};
+use tree_sitter::Node;
+
+use crate::line_index::LineIndex;
+use crate::parse;
+
+const BLOCK_KINDS: &[&str] = &[
+    "function_definition",
+    "macro_definition",
+    "module_definition",
+    "struct_definition",
+    "if_statement",
+    "for_statement",
+    "while_statement",
+    "let_statement",
+    "try_statement",
+    "compound_statement",
+    "quote_statement",
+    "do_clause",
+];
+
+const CLAUSE_KINDS: &[&str] = &[
+    "elseif_clause",
+    "else_clause",
+    "catch_clause",
+    "finally_clause",
+];
+
+pub fn format_source(src: &str, opts: FormattingOptions) -> Vec<TextEdit> {
+    let Ok(tree) = parse::parse(src, None) else {
+        return Vec::new();
+    };
+    format_tree(src, tree.root_node(), opts)
+}
+
+// Indentation still comes from the whole tree; only the edits are limited,
+// each whole-line edit being split into its indentation and trailing
+// whitespace parts so nothing outside `range` is touched. A range that
+// starts or ends inside a multi-line string or block comment is widened to
+// the whole literal first.
+pub fn format_range(src: &str, range: Range, opts: FormattingOptions) -> Vec<TextEdit> {
+    let Ok(tree) = parse::parse(src, None) else {
+        return Vec::new();
+    };
+    let root = tree.root_node();
+    let idx = LineIndex::new(src);
+    let mut range = range;
+    if let Some(lit) = idx
+        .to_byte_offset(range.start)
+        .and_then(|at| enclosing_literal(root, at))
+    {
+        range.start = range.start.min(idx.to_pos(lit.start_byte()));
+    }
+    if let Some(lit) = idx
+        .to_byte_offset(range.end)
+        .and_then(|at| enclosing_literal(root, at))
+    {
+        range.end = range.end.max(idx.to_pos(lit.end_byte()));
+    }
+    format_tree(src, root, opts)
+        .into_iter()
+        .flat_map(|edit| split_line_edit(src, &idx, edit))
+        .filter(|e| e.range.start >= range.start && e.range.end <= range.end)
+        .collect()
+}
+
+fn enclosing_literal(root: Node, at: usize) -> Option<Node> {
+    let mut node = root.descendant_for_byte_range(at, at);
+    while let Some(n) = node {
+        if matches!(
+            n.kind(),
+            "string_literal" | "command_literal" | "prefixed_string_literal" | "block_comment"
+        ) {
+            return Some(n);
+        }
+        node = n.parent();
+    }
+    None
+}
+
+// A single-line edit from format_tree only ever re-indents the line and
+// drops trailing whitespace; this turns it into one edit per change.
+fn split_line_edit(src: &str, idx: &LineIndex, edit: TextEdit) -> Vec<TextEdit> {
+    let row = edit.range.start.line;
+    if edit.range.end.line != row
+        || edit.range.start.character != 0
+        || row as usize >= idx.line_count()
+    {
+        return vec![edit];
+    }
+    let line_start = idx.line_start(row as usize);
+    let line = &src[line_start..idx.line_end(row as usize)];
+    let old_indent = line.len() - line.trim_start().len();
+    let new_indent = edit.new_text.len() - edit.new_text.trim_start().len();
+    let content = edit.new_text.trim_start();
+    if content.is_empty() || !line[old_indent..].starts_with(content) {
+        return vec![edit];
+    }
+    let mut out = Vec::new();
+    if line[..old_indent] != edit.new_text[..new_indent] {
+        out.push(TextEdit {
+            range: Range::new(Position::new(row, 0), Position::new(row, old_indent as u32)),
+            new_text: edit.new_text[..new_indent].to_string(),
+        });
+    }
+    let content_end = old_indent + content.len();
+    if content_end < line.len() {
+        out.push(TextEdit {
+            range: idx.range_of(line_start + content_end, line_start + line.len()),
+            new_text: String::new(),
+        });
+    }
+    out
+}
+
+fn format_tree(src: &str, root: Node, opts: FormattingOptions) -> Vec<TextEdit> {
+    if root.has_error() {
+        return Vec::new();
+    }
+
+    let unit = if opts.insert_spaces {
+        " ".repeat(opts.tab_size.max(1) as usize)
+    } else {
+        "\t".to_string()
+    };
+
+    let mut levels: HashMap<usize, usize> = HashMap::new();
+    collect_levels(src, root, 0, &mut levels);
+
+    // Whitespace at the end of a line that sits inside a multi-line string
+    // is part of the string's value and must survive formatting.
+    let mut verbatim_rows: HashSet<usize> = HashSet::new();
+    collect_verbatim_rows(root, &mut verbatim_rows);
+
+    let idx = LineIndex::new(src);
+    let mut line_starts = vec![0usize];
+    line_starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+
+    let mut edits = Vec::new();
+    let mut last_content_row: Option<usize> = None;
+    let mut last_content_end = 0usize;
+
+    for (row, &start) in line_starts.iter().enumerate() {
+        if start >= src.len() && row > 0 {
+            break;
+        }
+        let end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+        let line = src[start..end]
+            .strip_suffix('\r')
+            .unwrap_or(&src[start..end]);
+
+        let body = if verbatim_rows.contains(&row) {
+            line
+        } else {
+            line.trim_end()
+        };
+        let content = body.trim_start();
+        let indent = match levels.get(&row) {
+            Some(&level) if !content.is_empty() => unit.repeat(level),
+            _ if content.is_empty() => String::new(),
+            _ => body[..body.len() - content.len()].to_string(),
+        };
+        let formatted = format!("{indent}{content}");
+
+        if formatted != line {
+            edits.push(TextEdit {
+                range: idx.range_of(start, start + line.len()),
+                new_text: formatted.clone(),
+            });
+        }
+        if !formatted.is_empty() {
+            last_content_row = Some(row);
+            last_content_end = start + line.len();
+        }
+    }
+
+    // Collapse everything after the last non-blank line into one newline.
+    // Per-line edits on the dropped blank lines would overlap this one.
+    let Some(last_row) = last_content_row else {
+        return edits;
+    };
+    edits.retain(|e| e.range.start.line as usize <= last_row);
+    let tail = &src[last_content_end..];
+    let newline = if tail.starts_with("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    if tail != newline {
+        edits.push(TextEdit {
+            range: idx.range_of(last_content_end, src.len()),
+            new_text: newline.to_string(),
+        });
+    }
+    edits
+}
+
+// Records the indentation level of every line that starts with a statement
+// of a block (or with the block's own `end`/clause keyword). Other lines,
+// such as continuation lines of a long call, keep their indentation.
+fn collect_levels(src: &str, node: Node, level: usize, out: &mut HashMap<usize, usize>) {
+    let kind = node.kind();
+    let is_block = BLOCK_KINDS.contains(&kind);
+    let is_container = is_block || CLAUSE_KINDS.contains(&kind) || kind == "source_file";
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if is_container && starts_line(src, child) {
+            let child_kind = child.kind();
+            let dedent = (is_block && child_kind == "end") || CLAUSE_KINDS.contains(&child_kind);
+            let lvl = if dedent {
+                level.saturating_sub(1)
+            } else {
+                level
+            };
+            out.entry(child.start_position().row).or_insert(lvl);
+        }
+        let next = if BLOCK_KINDS.contains(&child.kind()) {
+            level + 1
+        } else {
+            level
+        };
+        collect_levels(src, child, next, out);
+    }
+}
+
+fn collect_verbatim_rows(node: Node, out: &mut HashSet<usize>) {
+    let start = node.start_position().row;
+    let end = node.end_position().row;
+    if matches!(
+        node.kind(),
+        "string_literal" | "command_literal" | "prefixed_string_literal"
+    ) && end > start
+    {
+        out.extend(start..end);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_verbatim_rows(child, out);
+    }
+}
+
+fn starts_line(src: &str, node: Node) -> bool {
+    let start = node.start_byte();
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    src[line_start..start]
+        .chars()
+        .all(|c| c == ' ' || c == '\t')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts() -> FormattingOptions {
+        FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        }
+    }
+
+    // Applied back to front so earlier edits keep their offsets.
+    fn apply(src: &str, edits: &[TextEdit]) -> String {
+        let idx = LineIndex::new(src);
+        let mut spans: Vec<(std::ops::Range<usize>, &str)> = edits
+            .iter()
+            .map(|e| (idx.to_byte_range(e.range).unwrap(), e.new_text.as_str()))
+            .collect();
+        spans.sort_by_key(|(r, _)| std::cmp::Reverse(r.start));
+        let mut out = src.to_string();
+        for (r, text) in spans {
+            out.replace_range(r, text);
+        }
+        out
+    }
+
+    #[test]
+    fn only_changed_lines_get_edits() {
+        let src = "function f(x)\n  y = x\n    return y\nend\n";
+        let edits = format_source(src, opts());
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start.line, 1);
+        assert_eq!(
+            apply(src, &edits),
+            "function f(x)\n    y = x\n    return y\nend\n"
+        );
+        assert!(format_source(&apply(src, &edits), opts()).is_empty());
+    }
+
+    #[test]
+    fn trailing_blank_lines_collapse_to_one_newline() {
+        assert_eq!(
+            apply(
+                "f(x) = 1\n\n  \n\n",
+                &format_source("f(x) = 1\n\n  \n\n", opts())
+            ),
+            "f(x) = 1\n"
+        );
+        assert_eq!(
+            apply("f(x) = 1", &format_source("f(x) = 1", opts())),
+            "f(x) = 1\n"
+        );
+        assert_eq!(
+            apply(
+                "f(x) = 1\r\n\r\n",
+                &format_source("f(x) = 1\r\n\r\n", opts())
+            ),
+            "f(x) = 1\r\n"
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_inside_multiline_strings_is_kept() {
+        let src = "x = \"\"\"\n  a   \n  b\n\"\"\"   \ny = 1  \n";
+        assert_eq!(
+            apply(src, &format_source(src, opts())),
+            "x = \"\"\"\n  a   \n  b\n\"\"\"\ny = 1\n"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_leave_the_file_unchanged() {
+        assert!(format_source("function f(x)\n  y = (\n", opts()).is_empty());
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    #[test]
+    fn range_edits_stay_inside_the_range() {
+        let src = "function f(x)\n  a = 1  \n  b = 2   \n  c = 3\nend\n";
+        let requested = range((2, 4), (2, 20));
+        let edits = format_range(src, requested, opts());
+        assert!(!edits.is_empty());
+        for e in &edits {
+            assert!(e.range.start >= requested.start && e.range.end <= requested.end);
+        }
+        // Line 2's indentation lies before the range; only its trailing
+        // whitespace goes.
+        assert_eq!(
+            apply(src, &edits),
+            "function f(x)\n  a = 1  \n  b = 2\n  c = 3\nend\n"
+        );
+    }
+
+    #[test]
+    fn range_widens_to_the_enclosing_block_comment() {
+        let src = "x = 1  \n#= a   \nb   \nc =#\ny = 2  \n";
+        // Starts and ends inside the comment, which spans lines 1 to 3.
+        let edits = format_range(src, range((2, 0), (2, 1)), opts());
+        assert_eq!(apply(src, &edits), "x = 1  \n#= a\nb\nc =#\ny = 2  \n");
+    }
+}
@@ -0,0 +1,297 @@
+use std::time::Duration;
+use tower_lsp::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Position, Range, SymbolKind};
+use tree_sitter::{Language, Node};
+
+use crate::index::SymbolIndex;
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+use crate::symbols::{is_definition_head, short_function_call};
+
+// Hints are best-effort and purely textual: there is no inference, so return
+// types are a plain `Any` placeholder, only literal right-hand sides get a
+// type, and parameter names come from whichever indexed methods share the
+// callee's name and accept the call's argument count.
+pub fn collect(
+    doc: &DocState,
+    lang: &Language,
+    range: Range,
+    index: &SymbolIndex,
+) -> Vec<InlayHint> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    // Editors often ask for a range ending past the last line; that covers
+    // the whole document.
+    let std::ops::Range { start, end } = idx.to_byte_range(range).unwrap_or(0..text.len());
+
+    let mut out = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        // Subtrees outside the requested range are never visited.
+        if node.end_byte() < start || node.start_byte() > end {
+            continue;
+        }
+        let hint = match node.kind() {
+            "function_definition" => missing_return_type(node).map(|at| (at, "Any")),
+            "assignment" if short_function_call(node).is_some() => {
+                missing_short_return_type(node).map(|at| (at, "Any"))
+            }
+            "assignment" => literal_assignment(node, &text),
+            "call_expression" if !is_definition_head(node) => {
+                for (at, name) in parameter_names(node, &text, index) {
+                    if (start..=end).contains(&at) {
+                        out.push(parameter_hint(idx.to_pos(at), &name));
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some((at, ty)) = hint
+            && (start..=end).contains(&at)
+        {
+            out.push(type_hint(idx.to_pos(at), ty));
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
+
+fn type_hint(position: Position, ty: &str) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(format!(":: {ty}")),
+        kind: Some(InlayHintKind::TYPE),
+        text_edits: None,
+        tooltip: None,
+        padding_left: Some(true),
+        padding_right: None,
+        data: None,
+    }
+}
+
+fn parameter_hint(position: Position, name: &str) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(format!("{name}:")),
+        kind: Some(InlayHintKind::PARAMETER),
+        text_edits: None,
+        tooltip: None,
+        padding_left: None,
+        padding_right: Some(true),
+        data: None,
+    }
+}
+
+// Byte offset just past the argument list of a definition with no
+// `::ReturnType`; `function f end` has no argument list and gets nothing.
+fn missing_return_type(def: Node) -> Option<usize> {
+    let sig = def.child_by_field_name("signature").or_else(|| {
+        let mut cursor = def.walk();
+        def.named_children(&mut cursor)
+            .find(|n| n.kind() == "signature")
+    })?;
+    let mut head = sig.named_child(0)?;
+    if head.kind() == "where_expression" {
+        head = head.named_child(0)?;
+    }
+    if head.kind() != "call_expression" {
+        return None;
+    }
+    let mut cursor = head.walk();
+    let args = head
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "argument_list")?;
+    Some(args.end_byte())
+}
+
+// `f(x) = x` and `f(x) where T = x`; `f(x)::T = x` already has a type.
+fn missing_short_return_type(node: Node) -> Option<usize> {
+    let mut head = node.named_child(0)?;
+    if head.kind() == "where_expression" {
+        head = head.named_child(0)?;
+    }
+    if head.kind() != "call_expression" {
+        return None;
+    }
+    let mut cursor = head.walk();
+    let args = head
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "argument_list")?;
+    Some(args.end_byte())
+}
+
+// Positional arguments of a call paired with the parameter name every
+// candidate method agrees on. Keyword arguments, anything after a splat (its
+// positions are unknown) and arguments already spelled like the parameter
+// get nothing.
+fn parameter_names(call: Node, text: &str, index: &SymbolIndex) -> Vec<(usize, String)> {
+    let Some(callee) = callee_name(call, text) else {
+        return Vec::new();
+    };
+    let mut cursor = call.walk();
+    let Some(args) = call
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "argument_list")
+    else {
+        return Vec::new();
+    };
+    let mut positional = Vec::new();
+    let mut cursor = args.walk();
+    for arg in args.children(&mut cursor) {
+        match arg.kind() {
+            ";" | "splat_expression" => break,
+            "named_argument" => continue,
+            _ if arg.is_named() => positional.push(arg),
+            _ => {}
+        }
+    }
+    if positional.is_empty() {
+        return Vec::new();
+    }
+
+    let candidates: Vec<Vec<Parameter>> = index
+        .search_exact(callee, &[])
+        .iter()
+        .filter(|e| e.kind == SymbolKind::FUNCTION)
+        .filter_map(|e| e.signature.as_deref().and_then(parse_parameters))
+        .filter(|params| accepts(params, positional.len()))
+        .collect();
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = Vec::new();
+    for (i, arg) in positional.into_iter().enumerate() {
+        let mut names = candidates.iter().map(|params| name_at(params, i));
+        let Some(Some(name)) = names.next() else {
+            continue;
+        };
+        if names.any(|other| other != Some(name)) || &text[arg.byte_range()] == name {
+            continue;
+        }
+        out.push((arg.start_byte(), name.to_string()));
+    }
+    out
+}
+
+fn callee_name<'a>(call: Node, text: &'a str) -> Option<&'a str> {
+    let callee = call.named_child(0)?;
+    let leaf = match callee.kind() {
+        "identifier" => callee,
+        "field_expression" => callee.named_child(callee.named_child_count().checked_sub(1)?)?,
+        _ => return None,
+    };
+    (leaf.kind() == "identifier").then(|| &text[leaf.byte_range()])
+}
+
+struct Parameter {
+    // None for anonymous (`::Type{T}`) and destructured (`(a, b)`) parameters.
+    name: Option<String>,
+    optional: bool,
+    vararg: bool,
+}
+
+fn accepts(params: &[Parameter], count: usize) -> bool {
+    let required = params.iter().filter(|p| !p.optional && !p.vararg).count();
+    let vararg = params.last().is_some_and(|p| p.vararg);
+    count >= required && (count <= params.len() || vararg)
+}
+
+// A vararg only labels the first argument it swallows.
+fn name_at(params: &[Parameter], i: usize) -> Option<&str> {
+    params.get(i)?.name.as_deref()
+}
+
+// Positional parameters of an indexed signature such as
+// `(x::Int, y = 2, zs...; kw = 1)::T where T`.
+fn parse_parameters(signature: &str) -> Option<Vec<Parameter>> {
+    let body = signature.strip_prefix('(')?;
+    let mut pieces = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut piece_start = 0;
+    let mut closed = false;
+    for (i, c) in body.char_indices() {
+        if in_string {
+            in_string = c != '"';
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            ')' | ';' | ',' if depth == 0 => {
+                pieces.push(&body[piece_start..i]);
+                piece_start = i + 1;
+                if c != ',' {
+                    closed = true;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    if !closed {
+        return None;
+    }
+    Some(
+        pieces
+            .into_iter()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(parse_parameter)
+            .collect(),
+    )
+}
+
+fn parse_parameter(piece: &str) -> Parameter {
+    let (decl, optional) = match piece.split_once('=') {
+        Some((decl, _)) => (decl.trim(), true),
+        None => (piece, false),
+    };
+    // `xs...` and `xs::Int...` alike.
+    let (decl, vararg) = match decl.strip_suffix("...") {
+        Some(decl) => (decl, true),
+        None => (decl, false),
+    };
+    let decl = decl.split_once("::").map_or(decl, |(name, _)| name).trim();
+    let name = (!decl.is_empty()
+        && decl
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '!'))
+    .then(|| decl.to_string());
+    Parameter {
+        name,
+        optional,
+        vararg,
+    }
+}
+
+fn literal_assignment(node: Node, text: &str) -> Option<(usize, &'static str)> {
+    let lhs = node.named_child(0)?;
+    if lhs.kind() != "identifier" {
+        return None;
+    }
+    let rhs = node.named_child(node.named_child_count().checked_sub(1)?)?;
+    let literal = &text[rhs.byte_range()];
+    let ty = match rhs.kind() {
+        // Hex, octal and binary literals are unsigned with a width that
+        // depends on the digit count; only plain decimals are certain.
+        "integer_literal" if literal.bytes().all(|b| b.is_ascii_digit() || b == b'_') => "Int64",
+        "float_literal" if literal.contains('f') => "Float32",
+        "float_literal" => "Float64",
+        "string_literal" => "String",
+        "character_literal" => "Char",
+        "boolean_literal" => "Bool",
+        "quote_expression" if rhs.named_child(0).is_some_and(|n| n.kind() == "identifier") => {
+            "Symbol"
+        }
+        _ => return None,
+    };
+    Some((lhs.end_byte(), ty))
+}
@@ -1,8 +1,12 @@
 use dashmap::DashMap;
+use globset::GlobMatcher;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tower_lsp::lsp_types::{Location, Range, SymbolInformation, SymbolKind, Url};
 
+const ACTIVE_DOC_BONUS: i64 = 20;
+
 #[derive(Clone)]
 pub struct SymbolEntry {
     pub name: Arc<str>,
@@ -11,6 +15,145 @@ pub struct SymbolEntry {
     pub path: PathBuf,
     pub range: Range,
     pub kind: SymbolKind,
+    pub container: Option<Arc<str>>,
+    // Argument list of a method, e.g. `(x::Int)`, so methods of one function
+    // can be told apart.
+    pub signature: Option<Arc<str>>,
+    // Declared supertype of a struct or abstract type, by name only:
+    // `<: Base.AbstractVector{T}` is recorded as `AbstractVector`.
+    pub supertype: Option<Arc<str>>,
+}
+
+#[derive(Default)]
+pub struct IndexStats {
+    pub documents: usize,
+    pub symbols: usize,
+    // Keyed by the LSP kind name, e.g. "Function".
+    pub by_kind: BTreeMap<String, usize>,
+}
+
+// What a symbol search is restricted to. Empty lists and `None` leave that
+// part unfiltered, so the default filter matches everything.
+#[derive(Default)]
+pub struct SymbolFilter<'a> {
+    pub roots: &'a [Url],
+    pub kinds: &'a [SymbolKind],
+    // Matched against the symbol's file path.
+    pub file_glob: Option<GlobMatcher>,
+    // Exact name of the enclosing module or struct.
+    pub container: Option<&'a str>,
+}
+
+impl SymbolFilter<'_> {
+    fn matches(&self, e: &SymbolEntry) -> bool {
+        e.within_roots(self.roots)
+            && e.has_kind(self.kinds)
+            && self
+                .file_glob
+                .as_ref()
+                .is_none_or(|g| !e.path.as_os_str().is_empty() && g.is_match(&e.path))
+            && self
+                .container
+                .is_none_or(|c| e.container.as_deref() == Some(c))
+    }
+}
+
+// A symbol as extraction finds it, before the index turns it into a
+// SymbolEntry.
+pub struct ExtractedSymbol {
+    pub info: SymbolInformation,
+    pub signature: Option<String>,
+    pub supertype: Option<String>,
+}
+
+impl From<SymbolInformation> for ExtractedSymbol {
+    fn from(info: SymbolInformation) -> Self {
+        Self {
+            info,
+            signature: None,
+            supertype: None,
+        }
+    }
+}
+
+impl SymbolEntry {
+    pub fn new(
+        name: String,
+        uri: Url,
+        path: PathBuf,
+        range: Range,
+        kind: SymbolKind,
+        container: Option<String>,
+    ) -> Self {
+        let name_lowercase: Arc<str> = Arc::from(lowercase_chars(&name));
+        Self {
+            name: Arc::from(name),
+            name_lowercase,
+            uri,
+            path,
+            range,
+            kind,
+            container: container.map(Arc::from),
+            signature: None,
+            supertype: None,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Option<String>) -> Self {
+        self.signature = signature.map(Arc::from);
+        self
+    }
+
+    pub fn with_supertype(mut self, supertype: Option<String>) -> Self {
+        self.supertype = supertype.map(Arc::from);
+        self
+    }
+
+    // Methods of one function are listed as `f(x::Int)` and `f(x::Float64)`,
+    // so a symbol picker can tell them apart.
+    pub fn to_symbol_information(&self) -> SymbolInformation {
+        let name = match &self.signature {
+            Some(signature) => format!("{}{}", self.name, signature),
+            None => self.name.to_string(),
+        };
+        #[allow(deprecated)]
+        SymbolInformation {
+            name,
+            kind: self.kind,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: self.uri.clone(),
+                range: self.range,
+            },
+            container_name: self.container.as_deref().map(str::to_string),
+        }
+    }
+
+    // An empty root list means no filtering.
+    pub fn within_roots(&self, roots: &[Url]) -> bool {
+        roots.is_empty() || roots.iter().any(|r| self.within_root(r))
+    }
+
+    // An empty kind list means no filtering.
+    pub fn has_kind(&self, kinds: &[SymbolKind]) -> bool {
+        kinds.is_empty() || kinds.contains(&self.kind)
+    }
+
+    pub fn within_root(&self, root: &Url) -> bool {
+        if root.scheme() == "file"
+            && self.uri.scheme() == "file"
+            && let Ok(root_path) = root.to_file_path()
+            && !self.path.as_os_str().is_empty()
+        {
+            return self.path.starts_with(root_path);
+        }
+        let base = root.as_str().trim_end_matches('/');
+        match self.uri.as_str().strip_prefix(base) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
 }
 
 pub struct SymbolIndex {
@@ -26,30 +169,125 @@ impl Default for SymbolIndex {
 }
 
 impl SymbolIndex {
-    pub fn upsert_doc(&self, doc_uri: &Url, symbols: Vec<SymbolInformation>) {
+    pub fn upsert_doc(&self, doc_uri: &Url, symbols: Vec<ExtractedSymbol>) {
         let mut out: Vec<SymbolEntry> = Vec::with_capacity(symbols.len());
         let path = doc_uri.to_file_path().ok().unwrap_or_default();
         for symbol in symbols {
-            let name: Arc<str> = Arc::from(symbol.name);
-            let name_lowercase: Arc<str> = Arc::from(name.to_ascii_lowercase());
-            out.push(SymbolEntry {
-                name,
-                name_lowercase,
-                uri: symbol.location.uri,
-                path: path.clone(),
-                range: symbol.location.range,
-                kind: symbol.kind,
-            });
+            let info = symbol.info;
+            out.push(
+                SymbolEntry::new(
+                    info.name,
+                    info.location.uri,
+                    path.clone(),
+                    info.location.range,
+                    info.kind,
+                    info.container_name,
+                )
+                .with_signature(symbol.signature)
+                .with_supertype(symbol.supertype),
+            );
+        }
+        self.insert_entries(doc_uri, out);
+    }
+
+    pub fn insert_entries(&self, doc_uri: &Url, entries: Vec<SymbolEntry>) {
+        self.by_doc.insert(doc_uri.to_string(), entries.into());
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, Arc<[SymbolEntry]>)> {
+        self.by_doc
+            .iter()
+            .map(|kv| (kv.key().clone(), Arc::clone(kv.value())))
+            .collect()
+    }
+
+    pub fn remove_doc(&self, doc_uri: &Url) {
+        self.by_doc.remove(doc_uri.as_str());
+    }
+
+    pub fn clear(&self) {
+        self.by_doc.clear();
+    }
+
+    pub fn doc_uris(&self) -> Vec<String> {
+        self.by_doc.iter().map(|kv| kv.key().clone()).collect()
+    }
+
+    // What is currently indexed, counted without touching any document.
+    pub fn stats(&self) -> IndexStats {
+        let mut stats = IndexStats {
+            documents: self.by_doc.len(),
+            ..IndexStats::default()
+        };
+        for kv in self.by_doc.iter() {
+            stats.symbols += kv.value().len();
+            for e in kv.value().iter() {
+                *stats.by_kind.entry(format!("{:?}", e.kind)).or_default() += 1;
+            }
+        }
+        stats
+    }
+
+    // Types whose declared supertype is `name`.
+    pub fn subtypes_of(&self, name: &str) -> Vec<SymbolEntry> {
+        let mut out = Vec::new();
+        for kv in self.by_doc.iter() {
+            out.extend(
+                kv.value()
+                    .iter()
+                    .filter(|e| e.supertype.as_deref() == Some(name))
+                    .cloned(),
+            );
+        }
+        out
+    }
+
+    pub fn search_exact(&self, name: &str, roots: &[Url]) -> Vec<SymbolEntry> {
+        let mut out = Vec::new();
+        for kv in self.by_doc.iter() {
+            for e in kv.value().iter() {
+                if e.name.as_ref() == name && e.within_roots(roots) {
+                    out.push(e.clone());
+                }
+            }
+        }
+        out
+    }
+
+    // Julia names are case-sensitive, so this is only a fallback for when
+    // search_exact finds nothing.
+    pub fn search_exact_case_insensitive(&self, name: &str, roots: &[Url]) -> Vec<SymbolEntry> {
+        let name_lc = lowercase_chars(name);
+        let mut out = Vec::new();
+        for kv in self.by_doc.iter() {
+            for e in kv.value().iter() {
+                if *e.name_lowercase == *name_lc && e.within_roots(roots) {
+                    out.push(e.clone());
+                }
+            }
+        }
+        out
+    }
+
+    pub fn search_by_kind(&self, kind: SymbolKind, roots: &[Url]) -> Vec<SymbolEntry> {
+        let mut out = Vec::new();
+        for kv in self.by_doc.iter() {
+            for e in kv.value().iter() {
+                if e.kind == kind && e.within_roots(roots) {
+                    out.push(e.clone());
+                }
+            }
         }
-        self.by_doc.insert(doc_uri.to_string(), out.into());
+        out
     }
 
-    pub fn search_fuzzy(
+    pub fn search_with_filter(
         &self,
         query: &str,
-        root: Option<&std::path::Path>,
+        filter: &SymbolFilter,
+        active_doc: Option<&str>,
         limit: usize,
-    ) -> Vec<tower_lsp::lsp_types::SymbolInformation> {
+    ) -> Vec<SymbolEntry> {
         if limit == 0 {
             return Vec::new();
         }
@@ -65,8 +303,8 @@ impl SymbolIndex {
             let mut out = Vec::with_capacity(limit.min(256));
             'outer: for blk in &blocks {
                 for e in blk.iter() {
-                    if root.is_none_or(|r| e.path.starts_with(r)) {
-                        out.push(to_lsp(e));
+                    if filter.matches(e) {
+                        out.push(e.clone());
                         if out.len() >= limit {
                             break 'outer;
                         }
@@ -76,24 +314,31 @@ impl SymbolIndex {
             return out;
         }
 
-        let qlc = q.to_ascii_lowercase();
+        let qlc = lowercase_chars(q);
 
         type Key = (i64, i64, i64, usize, usize);
         let mut heap: std::collections::BinaryHeap<std::cmp::Reverse<Key>> =
-            std::collections::BinaryHeap::new();
+            std::collections::BinaryHeap::with_capacity(limit.min(4096));
         let mut idx_counter: usize = 0;
 
         for (bi, blk) in blocks.iter().enumerate() {
             for (ei, e) in blk.iter().enumerate() {
                 idx_counter = idx_counter.wrapping_add(1);
-                if !root.is_none_or(|r| e.path.starts_with(r)) {
+                if !filter.matches(e) {
                     continue;
                 }
-                if let Some(score) = fuzzy_score(&qlc, &e.name, &e.name_lowercase) {
+                if let Some(mut score) = fuzzy_score(q, &qlc, &e.name, &e.name_lowercase) {
+                    if active_doc.is_some_and(|a| e.uri.as_str() == a) {
+                        score += ACTIVE_DOC_BONUS;
+                    }
                     let key: Key = (score, -(e.name.len() as i64), -(idx_counter as i64), bi, ei);
-                    heap.push(std::cmp::Reverse(key));
-                    if heap.len() > limit {
-                        let _ = heap.pop();
+                    if heap.len() < limit {
+                        heap.push(std::cmp::Reverse(key));
+                    } else if let Some(mut min) = heap.peek_mut() {
+                        // Full heap: only displace the current minimum, never grow past limit.
+                        if key > min.0 {
+                            *min = std::cmp::Reverse(key);
+                        }
                     }
                 }
             }
@@ -104,56 +349,123 @@ impl SymbolIndex {
 
         let mut out = Vec::with_capacity(keys.len());
         for (_sc, _neg_len, _neg_idx, bi, ei) in keys {
-            let e = &blocks[bi][ei];
-            out.push(to_lsp(e));
+            out.push(blocks[bi][ei].clone());
         }
         out
     }
 }
 
-fn to_lsp(e: &SymbolEntry) -> SymbolInformation {
-    #[allow(deprecated)]
-    SymbolInformation {
-        name: e.name.to_string(),
-        kind: e.kind,
-        tags: None,
-        deprecated: None,
-        location: Location {
-            uri: e.uri.clone(),
-            range: e.range,
-        },
-        container_name: None,
+// Julia has no scope information in the index, so references are textual
+// matches on identifier name across every indexed document.
+type Occurrences = HashMap<Arc<str>, Vec<(Range, bool)>>;
+
+#[derive(Default)]
+pub struct ReferenceIndex {
+    by_doc: DashMap<String, Arc<Occurrences>>,
+}
+
+impl ReferenceIndex {
+    pub fn upsert_doc(&self, doc_uri: &Url, occurrences: Vec<(String, Range, bool)>) {
+        let mut by_name: Occurrences = HashMap::new();
+        for (name, range, is_declaration) in occurrences {
+            by_name
+                .entry(Arc::from(name))
+                .or_default()
+                .push((range, is_declaration));
+        }
+        self.by_doc.insert(doc_uri.to_string(), Arc::new(by_name));
+    }
+
+    pub fn remove_doc(&self, doc_uri: &Url) {
+        self.by_doc.remove(doc_uri.as_str());
+    }
+
+    pub fn clear(&self) {
+        self.by_doc.clear();
+    }
+
+    pub fn doc_uris(&self) -> Vec<String> {
+        self.by_doc.iter().map(|kv| kv.key().clone()).collect()
+    }
+
+    // The inverse of upsert_doc, for writing the cache.
+    pub fn occurrences(&self, doc_uri: &str) -> Vec<(String, Range, bool)> {
+        let Some(by_name) = self.by_doc.get(doc_uri) else {
+            return Vec::new();
+        };
+        by_name
+            .iter()
+            .flat_map(|(name, ranges)| {
+                ranges
+                    .iter()
+                    .map(|&(range, is_declaration)| (name.to_string(), range, is_declaration))
+            })
+            .collect()
+    }
+
+    pub fn find_references(&self, name: &str, include_declaration: bool) -> Vec<Location> {
+        let mut out = Vec::new();
+        for kv in self.by_doc.iter() {
+            let Some(ranges) = kv.value().get(name) else {
+                continue;
+            };
+            let Ok(uri) = Url::parse(kv.key()) else {
+                continue;
+            };
+            for (range, is_declaration) in ranges {
+                if include_declaration || !is_declaration {
+                    out.push(Location {
+                        uri: uri.clone(),
+                        range: *range,
+                    });
+                }
+            }
+        }
+        out
     }
 }
 
+// Lowercases char by char so the result has exactly as many chars as the
+// input, which lets fuzzy_score walk a name and its lowercase form in step.
+fn lowercase_chars(s: &str) -> String {
+    s.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
 // GPT Magic
-fn fuzzy_score(q_lc: &str, name: &str, name_lc: &str) -> Option<i64> {
+fn fuzzy_score(q: &str, q_lc: &str, name: &str, name_lc: &str) -> Option<i64> {
     if q_lc.is_empty() {
         return Some(0);
     }
-    let qb = q_lc.as_bytes();
-    let nb = name.as_bytes();
-    let nblc = name_lc.as_bytes();
+    let qc: Vec<char> = q_lc.chars().collect();
+    let q_orig: Vec<char> = q.chars().collect();
+    let nc: Vec<char> = name.chars().collect();
 
     let mut qi = 0usize;
     let mut score: i64 = 0;
     let mut last_match: Option<usize> = None;
 
-    for i in 0..nblc.len() {
-        if qi >= qb.len() {
+    for (i, c) in name_lc.chars().enumerate() {
+        if qi >= qc.len() {
             break;
         }
-        if nblc[i] == qb[qi] {
+        if c == qc[qi] {
             let mut s: i64 = 10;
 
-            let prev = if i == 0 { b' ' } else { nb[i - 1] };
+            let prev = if i == 0 { ' ' } else { nc[i - 1] };
             if is_boundary(prev) {
                 s += 15;
             }
 
-            if i > 0 && nb[i].is_ascii_uppercase() && nb[i - 1].is_ascii_lowercase() {
+            if i > 0 && nc[i].is_uppercase() && nc[i - 1].is_lowercase() {
                 s += 12;
             }
+            // Matching is case-insensitive, but typing the name's own case
+            // puts `Vector` above `vector` for the query "Vector".
+            if nc.get(i) == q_orig.get(qi) {
+                s += 3;
+            }
             if let Some(last) = last_match {
                 if i == last + 1 {
                     s += 8;
@@ -172,12 +484,272 @@ fn fuzzy_score(q_lc: &str, name: &str, name_lc: &str) -> Option<i64> {
             qi += 1;
         }
     }
-    if qi == qb.len() { Some(score) } else { None }
+    if qi == qc.len() { Some(score) } else { None }
+}
+
+fn is_boundary(c: char) -> bool {
+    matches!(c, ' ' | '_' | '-' | '/' | '.' | '(' | ')' | '[' | ']')
 }
 
-fn is_boundary(b: u8) -> bool {
-    matches!(
-        b,
-        b' ' | b'_' | b'-' | b'/' | b'.' | b'(' | b')' | b'[' | b']'
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, kind: SymbolKind) -> SymbolEntry {
+        entry_at("/ws/a.jl", name, kind)
+    }
+
+    fn entry_at(path: &str, name: &str, kind: SymbolKind) -> SymbolEntry {
+        SymbolEntry::new(
+            name.to_string(),
+            Url::from_file_path(path).unwrap(),
+            PathBuf::from(path),
+            Range::default(),
+            kind,
+            None,
+        )
+    }
+
+    #[test]
+    fn methods_are_labelled_with_their_signature() {
+        let int = entry("area", SymbolKind::FUNCTION).with_signature(Some("(x::Int)".into()));
+        let float = entry("area", SymbolKind::FUNCTION).with_signature(Some("(x::Float64)".into()));
+        assert_eq!(int.to_symbol_information().name, "area(x::Int)");
+        assert_eq!(float.to_symbol_information().name, "area(x::Float64)");
+        assert_eq!(
+            entry("Shape", SymbolKind::STRUCT)
+                .to_symbol_information()
+                .name,
+            "Shape"
+        );
+    }
+
+    #[test]
+    fn search_exact_matches_case_and_roots() {
+        let index = SymbolIndex::default();
+        let uri = Url::parse("file:///ws/a.jl").unwrap();
+        index.insert_entries(
+            &uri,
+            vec![
+                entry("Shape", SymbolKind::STRUCT),
+                entry("shape", SymbolKind::FUNCTION),
+            ],
+        );
+        let found = index.search_exact("Shape", &[]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, SymbolKind::STRUCT);
+        let other = Url::parse("file:///elsewhere/").unwrap();
+        assert!(index.search_exact("Shape", &[other]).is_empty());
+    }
+
+    #[test]
+    fn removed_docs_leave_the_others_searchable() {
+        let index = SymbolIndex::default();
+        let a = Url::parse("file:///ws/a.jl").unwrap();
+        let b = Url::parse("file:///ws/b.jl").unwrap();
+        index.insert_entries(&a, vec![entry("area", SymbolKind::FUNCTION)]);
+        index.insert_entries(&b, vec![entry_at("/ws/b.jl", "area", SymbolKind::FUNCTION)]);
+        assert_eq!(index.search_exact("area", &[]).len(), 2);
+
+        index.remove_doc(&a);
+        let found = index.search_exact("area", &[]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].uri, b);
+        assert_eq!(index.doc_uris(), [b.to_string()]);
+
+        index.clear();
+        assert!(index.doc_uris().is_empty());
+    }
+
+    #[test]
+    fn exact_case_ranks_first() {
+        let index = SymbolIndex::default();
+        let uri = Url::parse("file:///ws/a.jl").unwrap();
+        index.insert_entries(
+            &uri,
+            vec![
+                entry("vector", SymbolKind::VARIABLE),
+                entry("Vector", SymbolKind::STRUCT),
+            ],
+        );
+        let filter = SymbolFilter {
+            roots: &[],
+            kinds: &[],
+            file_glob: None,
+            container: None,
+        };
+        let names = |query: &str| -> Vec<String> {
+            index
+                .search_with_filter(query, &filter, None, 10)
+                .iter()
+                .map(|e| e.name.to_string())
+                .collect()
+        };
+        assert_eq!(names("Vector"), ["Vector", "vector"]);
+        assert_eq!(names("vector"), ["vector", "Vector"]);
+    }
+
+    #[test]
+    fn active_document_ranks_first() {
+        let index = SymbolIndex::default();
+        for path in ["/ws/a.jl", "/ws/b.jl"] {
+            let uri = Url::from_file_path(path).unwrap();
+            index.insert_entries(&uri, vec![entry_at(path, "render", SymbolKind::FUNCTION)]);
+        }
+        let filter = SymbolFilter {
+            roots: &[],
+            kinds: &[],
+            file_glob: None,
+            container: None,
+        };
+        for active in ["file:///ws/a.jl", "file:///ws/b.jl"] {
+            let found = index.search_with_filter("render", &filter, Some(active), 10);
+            assert_eq!(found.len(), 2);
+            assert_eq!(found[0].uri.as_str(), active);
+        }
+    }
+
+    #[test]
+    fn custom_scheme_roots_compare_by_uri() {
+        let remote = |uri: &str| {
+            SymbolEntry::new(
+                "f".to_string(),
+                Url::parse(uri).unwrap(),
+                PathBuf::new(),
+                Range::default(),
+                SymbolKind::FUNCTION,
+                None,
+            )
+        };
+        let root = Url::parse("vscode-remote://host/ws").unwrap();
+        assert!(remote("vscode-remote://host/ws/src/a.jl").within_root(&root));
+        assert!(!remote("vscode-remote://host/ws2/a.jl").within_root(&root));
+        assert!(!remote("vscode-remote://other/ws/a.jl").within_root(&root));
+        let with_slash = Url::parse("vscode-remote://host/ws/").unwrap();
+        assert!(remote("vscode-remote://host/ws/a.jl").within_root(&with_slash));
+
+        let file_root = Url::parse("file:///ws").unwrap();
+        assert!(entry_at("/ws/a.jl", "f", SymbolKind::FUNCTION).within_root(&file_root));
+        assert!(!entry_at("/ws2/a.jl", "f", SymbolKind::FUNCTION).within_root(&file_root));
+    }
+
+    #[test]
+    fn fuzzy_score_matches_non_ascii_names() {
+        let score =
+            |q: &str, name: &str| fuzzy_score(q, &lowercase_chars(q), name, &lowercase_chars(name));
+        assert!(score("sg", "∇sigmoid").is_some());
+        assert!(score("∇s", "∇sigmoid").is_some());
+        assert!(score("σ²", "σ²_hat").is_some());
+        assert!(score("gs", "∇sigmoid").is_none());
+        // `s` right after `∇` sits on a word boundary.
+        assert!(score("s", "∇sigmoid") > score("g", "∇sigmoid"));
+    }
+
+    // cargo test --release -- --ignored --nocapture many_ties
+    #[test]
+    #[ignore]
+    fn bench_many_ties() {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+        use std::time::Instant;
+
+        const DOCS: usize = 1_000;
+        const PER_DOC: usize = 1_000;
+        const LIMIT: usize = 100;
+        let index = SymbolIndex::default();
+        for d in 0..DOCS {
+            let path = format!("/ws/f{d}.jl");
+            let uri = Url::from_file_path(&path).unwrap();
+            let entries = (0..PER_DOC)
+                .map(|_| entry_at(&path, "item", SymbolKind::FUNCTION))
+                .collect();
+            index.insert_entries(&uri, entries);
+        }
+        let filter = SymbolFilter {
+            roots: &[],
+            kinds: &[],
+            file_glob: None,
+            container: None,
+        };
+        let t = Instant::now();
+        let found = index.search_with_filter("i", &filter, None, LIMIT);
+        let search = t.elapsed();
+        assert_eq!(found.len(), LIMIT);
+
+        // The heap alone over the same stream of tied keys, both ways: push
+        // then pop on a heap that grows as needed, as search_with_filter
+        // used to, and compare-first on one sized up front, as it does now.
+        // Every change of capacity is one allocation of the heap's buffer,
+        // and with_capacity counts as one.
+        let keys = || (0..DOCS * PER_DOC).map(|i| Reverse((25i64, -4i64, -(i as i64))));
+        let t = Instant::now();
+        let mut heap = BinaryHeap::new();
+        let (mut pushes, mut allocs, mut capacity) = (0usize, 0usize, heap.capacity());
+        for key in keys() {
+            heap.push(key);
+            pushes += 1;
+            if heap.capacity() != capacity {
+                capacity = heap.capacity();
+                allocs += 1;
+            }
+            if heap.len() > LIMIT {
+                heap.pop();
+            }
+        }
+        let push_pop = t.elapsed();
+        let (push_pop_pushes, push_pop_allocs) = (pushes, allocs);
+
+        let t = Instant::now();
+        let mut heap = BinaryHeap::with_capacity(LIMIT);
+        let (mut pushes, mut allocs, mut capacity) = (0usize, 1usize, heap.capacity());
+        for key in keys() {
+            if heap.len() < LIMIT {
+                heap.push(key);
+                pushes += 1;
+                if heap.capacity() != capacity {
+                    capacity = heap.capacity();
+                    allocs += 1;
+                }
+            } else if let Some(mut min) = heap.peek_mut()
+                && key.0 > min.0
+            {
+                *min = key;
+                pushes += 1;
+            }
+        }
+        let compare_first = t.elapsed();
+        assert_eq!(heap.len(), LIMIT);
+        assert!(allocs <= push_pop_allocs);
+        println!(
+            "{} tied entries, limit {LIMIT}: search_with_filter {search:?}; heap alone: \
+             push-then-pop {push_pop:?} ({push_pop_pushes} pushes, {push_pop_allocs} allocations), \
+             compare-first {compare_first:?} ({pushes} writes, {allocs} allocations)",
+            DOCS * PER_DOC
+        );
+    }
+
+    #[test]
+    fn case_insensitive_and_by_kind_lookups() {
+        let index = SymbolIndex::default();
+        let uri = Url::parse("file:///ws/a.jl").unwrap();
+        index.insert_entries(
+            &uri,
+            vec![
+                entry("Shape", SymbolKind::STRUCT),
+                entry("shape", SymbolKind::FUNCTION),
+                entry("Geometry", SymbolKind::MODULE),
+            ],
+        );
+        assert_eq!(index.search_exact_case_insensitive("SHAPE", &[]).len(), 2);
+        assert!(index.search_exact_case_insensitive("shap", &[]).is_empty());
+        let modules = index.search_by_kind(SymbolKind::MODULE, &[]);
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].name.as_ref(), "Geometry");
+        let other = Url::parse("file:///elsewhere/").unwrap();
+        assert!(
+            index
+                .search_by_kind(SymbolKind::MODULE, &[other])
+                .is_empty()
+        );
+    }
 }
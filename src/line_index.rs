@@ -0,0 +1,306 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range};
+
+// What `character` in an LSP position counts. Negotiated once in
+// `initialize`; a server process only ever talks to one client. UTF-16 is
+// the protocol default, so it applies until a client offers UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+}
+
+static UTF16_POSITIONS: AtomicBool = AtomicBool::new(true);
+
+impl PositionEncoding {
+    pub fn from_client(offered: Option<&[PositionEncodingKind]>) -> Self {
+        if offered.is_some_and(|kinds| kinds.contains(&PositionEncodingKind::UTF8)) {
+            Self::Utf8
+        } else {
+            Self::Utf16
+        }
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    pub fn negotiated() -> Self {
+        if UTF16_POSITIONS.load(Ordering::Relaxed) {
+            Self::Utf16
+        } else {
+            Self::Utf8
+        }
+    }
+
+    pub fn set_negotiated(self) {
+        UTF16_POSITIONS.store(self == Self::Utf16, Ordering::Relaxed);
+    }
+}
+
+// Maps byte offsets in a document to LSP positions and back, with
+// `character` counted in the negotiated encoding.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+    // End of each line's content, excluding the `\n` or `\r\n` terminator.
+    ends: Vec<usize>,
+    encoding: PositionEncoding,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self::with_encoding(text, PositionEncoding::negotiated())
+    }
+
+    pub fn with_encoding(text: &'a str, encoding: PositionEncoding) -> Self {
+        let bytes = text.as_bytes();
+        let mut starts = Vec::with_capacity(text.lines().count() + 1);
+        let mut ends = Vec::with_capacity(starts.capacity());
+        starts.push(0);
+        for (i, b) in bytes.iter().enumerate() {
+            if *b == b'\n' {
+                let end = if i > 0 && bytes[i - 1] == b'\r' {
+                    i - 1
+                } else {
+                    i
+                };
+                ends.push(end);
+                starts.push(i + 1);
+            }
+        }
+        ends.push(text.len());
+        Self {
+            text,
+            starts,
+            ends,
+            encoding,
+        }
+    }
+
+    // A trailing newline opens one more (empty) line, so "" and "a" have
+    // one line and "a\n" has two.
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn to_pos(&self, idx: usize) -> Position {
+        match self.encoding {
+            PositionEncoding::Utf8 => {
+                let i = self.line_of(idx);
+                Position {
+                    line: i as u32,
+                    character: (idx.min(self.ends[i]) - self.starts[i]) as u32,
+                }
+            }
+            PositionEncoding::Utf16 => self.to_pos_utf16(idx),
+        }
+    }
+
+    fn to_pos_utf16(&self, idx: usize) -> Position {
+        let i = self.line_of(idx);
+        let mut end = idx.min(self.ends[i]);
+        // An offset inside a multi-byte character counts from its start.
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let character = self.text[self.starts[i]..end]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+        Position {
+            line: i as u32,
+            character: character as u32,
+        }
+    }
+
+    pub fn range_of(&self, start: usize, end: usize) -> Range {
+        Range {
+            start: self.to_pos(start),
+            end: self.to_pos(end),
+        }
+    }
+
+    pub fn line_start(&self, line: usize) -> usize {
+        self.starts[line]
+    }
+
+    pub fn line_end(&self, line: usize) -> usize {
+        self.ends[line]
+    }
+
+    // None for a line past the end of the text. A character past the end of
+    // its line falls back to the line end, as the LSP spec asks, and one
+    // inside a multi-byte character to that character's start, so the result
+    // is always safe to slice at.
+    pub fn to_byte_offset(&self, pos: Position) -> Option<usize> {
+        let line = pos.line as usize;
+        let start = *self.starts.get(line)?;
+        let end = self.ends[line];
+        let character = pos.character as usize;
+        match self.encoding {
+            PositionEncoding::Utf8 => {
+                let mut offset = start.saturating_add(character).min(end);
+                while !self.text.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                Some(offset)
+            }
+            PositionEncoding::Utf16 => {
+                let mut units = 0;
+                for (i, c) in self.text[start..end].char_indices() {
+                    units += c.len_utf16();
+                    if units > character {
+                        return Some(start + i);
+                    }
+                }
+                Some(end)
+            }
+        }
+    }
+
+    pub fn to_byte_range(&self, range: Range) -> Option<std::ops::Range<usize>> {
+        let start = self.to_byte_offset(range.start)?;
+        let end = self.to_byte_offset(range.end)?;
+        (start <= end).then_some(start..end)
+    }
+
+    fn line_of(&self, idx: usize) -> usize {
+        match self.starts.binary_search(&idx) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf8(text: &str) -> LineIndex<'_> {
+        LineIndex::with_encoding(text, PositionEncoding::Utf8)
+    }
+
+    fn utf16(text: &str) -> LineIndex<'_> {
+        LineIndex::with_encoding(text, PositionEncoding::Utf16)
+    }
+
+    #[test]
+    fn empty_text_has_one_line() {
+        let idx = utf8("");
+        assert_eq!(idx.line_count(), 1);
+        assert_eq!(idx.to_pos(0), Position::new(0, 0));
+        assert_eq!(
+            idx.range_of(0, 0),
+            Range::new(Position::new(0, 0), Position::new(0, 0))
+        );
+    }
+
+    #[test]
+    fn text_without_newline() {
+        let idx = utf8("abc");
+        assert_eq!(idx.line_count(), 1);
+        assert_eq!(idx.to_pos(2), Position::new(0, 2));
+        assert_eq!(idx.to_pos(3), Position::new(0, 3));
+    }
+
+    #[test]
+    fn trailing_newline_opens_a_line() {
+        let idx = utf8("ab\n");
+        assert_eq!(idx.line_count(), 2);
+        assert_eq!(idx.to_pos(2), Position::new(0, 2));
+        assert_eq!(idx.to_pos(3), Position::new(1, 0));
+    }
+
+    #[test]
+    fn only_newlines() {
+        let idx = utf8("\n\n\n");
+        assert_eq!(idx.line_count(), 4);
+        for i in 0..=3 {
+            assert_eq!(idx.to_pos(i), Position::new(i as u32, 0));
+        }
+    }
+
+    #[test]
+    fn crlf_terminator_is_not_part_of_the_line() {
+        let idx = utf8("ab\r\ncd");
+        assert_eq!(idx.to_pos(3), Position::new(0, 2));
+        assert_eq!(idx.to_pos(4), Position::new(1, 0));
+        assert_eq!(idx.line_end(0), 2);
+    }
+
+    #[test]
+    fn utf16_counts_code_units() {
+        // `σ` is 2 bytes and 1 UTF-16 unit, `𝔸` 4 bytes and 2 units.
+        let text = "σ = 1\n𝔸x";
+        let idx = utf16(text);
+        assert_eq!(idx.to_pos(2), Position::new(0, 1));
+        assert_eq!(idx.to_pos(text.find('x').unwrap()), Position::new(1, 2));
+        assert_eq!(utf8(text).to_pos(2), Position::new(0, 2));
+        // Inside a character counts from its start.
+        assert_eq!(idx.to_pos(1), Position::new(0, 0));
+    }
+
+    #[test]
+    fn to_byte_offset_inverts_to_pos() {
+        let text = "a∇σ = 1\n𝔸 = α\n";
+        for idx in [utf8(text), utf16(text)] {
+            for (offset, _) in text.char_indices() {
+                assert_eq!(idx.to_byte_offset(idx.to_pos(offset)), Some(offset));
+            }
+        }
+    }
+
+    #[test]
+    fn to_byte_offset_reads_utf16_columns() {
+        let text = "∇f(x) = 1\n𝔸x";
+        let idx = utf16(text);
+        // `f` is the second UTF-16 unit, but starts at byte 3.
+        assert_eq!(idx.to_byte_offset(Position::new(0, 1)), Some(3));
+        assert_eq!(
+            idx.to_byte_offset(Position::new(1, 2)),
+            Some(text.len() - 1)
+        );
+        // Between the two units of `𝔸` falls back to its start.
+        assert_eq!(
+            idx.to_byte_offset(Position::new(1, 1)),
+            Some(text.find('𝔸').unwrap())
+        );
+    }
+
+    #[test]
+    fn to_byte_offset_clamps_and_rejects() {
+        let text = "ab\r\nσ";
+        for idx in [utf8(text), utf16(text)] {
+            assert_eq!(idx.to_byte_offset(Position::new(0, 99)), Some(2));
+            assert_eq!(idx.to_byte_offset(Position::new(1, 99)), Some(text.len()));
+            assert_eq!(idx.to_byte_offset(Position::new(2, 0)), None);
+        }
+        let idx = utf8(text);
+        assert_eq!(
+            idx.to_byte_range(Range::new(Position::new(1, 0), Position::new(0, 0))),
+            None
+        );
+        assert_eq!(
+            idx.to_byte_range(Range::new(Position::new(0, 1), Position::new(1, 2))),
+            Some(1..6)
+        );
+    }
+
+    #[test]
+    fn negotiation_prefers_utf8() {
+        let offered = [PositionEncodingKind::UTF16, PositionEncodingKind::UTF8];
+        assert_eq!(
+            PositionEncoding::from_client(Some(&offered)),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::from_client(Some(&[PositionEncodingKind::UTF16])),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(PositionEncoding::from_client(None), PositionEncoding::Utf16);
+    }
+}
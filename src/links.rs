@@ -0,0 +1,83 @@
+use std::time::Duration;
+use tower_lsp::lsp_types::{DocumentLink, Url};
+use tree_sitter::{Language, Node};
+
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+use crate::symbols::collect_named_descendants_by;
+
+// `include("file.jl")` and `include(M, "file.jl")`, resolved against the
+// including file's directory. Paths built at runtime (interpolation,
+// joinpath) are left alone, as are targets that do not exist on disk.
+pub fn collect(doc: &DocState, lang: &Language, uri: &Url) -> Vec<DocumentLink> {
+    let Some(dir) = uri
+        .to_file_path()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+    else {
+        return Vec::new();
+    };
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+
+    let mut out = Vec::new();
+    for call in
+        collect_named_descendants_by(tree.root_node(), &|n: &Node| n.kind() == "call_expression")
+    {
+        if !is_include(call, &text) {
+            continue;
+        }
+        let Some(path) = include_path(call) else {
+            continue;
+        };
+        let target = dir.join(&text[path.byte_range()]);
+        if !target.is_file() {
+            continue;
+        }
+        let Ok(target) = Url::from_file_path(&target) else {
+            continue;
+        };
+        out.push(DocumentLink {
+            range: idx.range_of(path.start_byte(), path.end_byte()),
+            target: Some(target),
+            tooltip: None,
+            data: None,
+        });
+    }
+    out
+}
+
+fn is_include(call: Node, text: &str) -> bool {
+    let Some(callee) = call.named_child(0) else {
+        return false;
+    };
+    let name = match callee.kind() {
+        "identifier" => callee,
+        "field_expression" => match callee
+            .named_child_count()
+            .checked_sub(1)
+            .and_then(|i| callee.named_child(i))
+        {
+            Some(last) => last,
+            None => return false,
+        },
+        _ => return false,
+    };
+    &text[name.byte_range()] == "include"
+}
+
+// The `content` node of a plain string literal as the last argument.
+fn include_path(call: Node) -> Option<Node> {
+    let mut cursor = call.walk();
+    let args = call
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "argument_list")?;
+    let last = args.named_child(args.named_child_count().checked_sub(1)?)?;
+    if last.kind() != "string_literal" || last.named_child_count() != 1 {
+        return None;
+    }
+    last.named_child(0).filter(|n| n.kind() == "content")
+}
@@ -1,23 +1,67 @@
-use std::path::PathBuf;
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{LspService, Server};
-use tracing::{info, warn};
-use tracing_appender::rolling;
-use tracing_subscriber::EnvFilter;
+use tracing::{debug, error, info, warn};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Registry, reload};
 
+mod cache;
+mod callhierarchy;
+mod codeactions;
+mod codelens;
+mod completion;
+mod config;
+mod definition;
 mod diagnostics;
+mod formatter;
+mod hints;
 mod index;
+mod line_index;
+mod links;
 mod parse;
+mod progress;
+mod semantic;
 mod state;
 mod symbols;
+mod typehierarchy;
 
+use clap::Parser;
+use config::ParsecConfig;
+use index::{SymbolEntry, SymbolFilter};
+use line_index::PositionEncoding;
 use state::ServerState;
 
+const COMPLETION_LIMIT: usize = 200;
+
+const REINDEX_COMMAND: &str = "parsec.reindexWorkspace";
+const CLEAR_CACHE_COMMAND: &str = "parsec.clearCache";
+const INDEX_STATS_COMMAND: &str = "parsec.showIndexStats";
+const INDEX_STATS_JSON_COMMAND: &str = "parsec.indexStats";
+
 struct Backend {
     client: tower_lsp::Client,
     state: Arc<ServerState>,
+    log_filter: reload::Handle<EnvFilter, Registry>,
+    // Set while a parsec.reindexWorkspace run is in flight.
+    reindexing: Arc<AtomicBool>,
+    work_done_progress: AtomicBool,
+    // Clients that pull diagnostics get none pushed, so they are not shown
+    // twice.
+    pull_diagnostics: AtomicBool,
+    definition_links: AtomicBool,
+    implementation_links: AtomicBool,
+    // lsp-types has no typeHierarchyProvider server capability, so type
+    // hierarchy is registered dynamically when the client allows it.
+    type_hierarchy_registration: AtomicBool,
+    // Without dynamic registration the client rejects the file watcher, and
+    // external changes are only seen on the next reindex.
+    watched_files_registration: AtomicBool,
 }
 
 #[tower_lsp::async_trait]
@@ -27,63 +71,440 @@ impl tower_lsp::LanguageServer for Backend {
         params: InitializeParams,
     ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
         info!("Initializing Parsec LSP Server.");
-        if let Some(root_dir) = workspace_root_from_params(&params) {
-            self.state.set_root(root_dir.clone());
-            self.state.start_indexer(root_dir);
-        } else {
-            warn!("No workspace root is provided. Background indexing is disabled.");
+        // The options outside ParsecConfig are only read here; see config.rs.
+        if let Some(boost) = params
+            .initialization_options
+            .as_ref()
+            .and_then(|o| o.get("boostActiveDoc"))
+            .and_then(|v| v.as_bool())
+        {
+            self.state.set_boost_active_doc(boost);
+        }
+        if let Some(ms) = params
+            .initialization_options
+            .as_ref()
+            .and_then(|o| o.get("watchedFilesCoalesceMs"))
+            .and_then(|v| v.as_u64())
+        {
+            self.state
+                .set_watch_coalesce(std::time::Duration::from_millis(ms));
         }
+        if let Some(exts) = params
+            .initialization_options
+            .as_ref()
+            .and_then(|o| o.get("fileExtensions"))
+            .and_then(|v| v.as_array())
+        {
+            self.state.set_file_extensions(
+                exts.iter()
+                    .filter_map(|e| e.as_str().map(str::to_string))
+                    .collect(),
+            );
+        }
+        if let Some(options) = &params.initialization_options {
+            self.apply_config(self.state.config().merge_lsp_value(options));
+        }
+        if let Some(bytes) = params
+            .initialization_options
+            .as_ref()
+            .and_then(|o| o.pointer("/index/maxFileSizeBytes"))
+            .and_then(|v| v.as_u64())
+        {
+            self.state.set_max_file_size(bytes);
+        }
+        let encoding = PositionEncoding::from_client(
+            params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|g| g.position_encodings.as_deref()),
+        );
+        encoding.set_negotiated();
+        info!("position encoding {:?}", encoding);
+        let work_done_progress = params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|w| w.work_done_progress)
+            .unwrap_or(false);
+        self.work_done_progress
+            .store(work_done_progress, Ordering::Relaxed);
+        let pull_diagnostics = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|t| t.diagnostic.is_some());
+        self.pull_diagnostics
+            .store(pull_diagnostics, Ordering::Relaxed);
+        let definition_links = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.definition.as_ref())
+            .and_then(|d| d.link_support)
+            .unwrap_or(false);
+        self.definition_links
+            .store(definition_links, Ordering::Relaxed);
+        let implementation_links = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.implementation.as_ref())
+            .and_then(|i| i.link_support)
+            .unwrap_or(false);
+        self.implementation_links
+            .store(implementation_links, Ordering::Relaxed);
+        let type_hierarchy_registration = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|t| t.type_hierarchy.as_ref())
+            .and_then(|h| h.dynamic_registration)
+            .unwrap_or(false);
+        self.type_hierarchy_registration
+            .store(type_hierarchy_registration, Ordering::Relaxed);
+        let watched_files_registration = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|w| w.did_change_watched_files.as_ref())
+            .and_then(|w| w.dynamic_registration)
+            .unwrap_or(false);
+        self.watched_files_registration
+            .store(watched_files_registration, Ordering::Relaxed);
+        self.state.set_roots(workspace_roots_from_params(&params));
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
                 name: "parsec".into(),
                 version: Some(env!("CARGO_PKG_VERSION").into()),
             }),
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
+                position_encoding: Some(encoding.kind()),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(true),
+                        })),
+                        ..Default::default()
+                    },
                 )),
                 document_symbol_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![".".into()]),
+                    ..Default::default()
+                }),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".into(), ",".into()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                }),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        REINDEX_COMMAND.to_string(),
+                        CLEAR_CACHE_COMMAND.to_string(),
+                        INDEX_STATS_COMMAND.to_string(),
+                        INDEX_STATS_JSON_COMMAND.to_string(),
+                        codelens::RUN_TESTSET_COMMAND.to_string(),
+                    ],
+                    work_done_progress_options: Default::default(),
+                }),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(
+                    true,
+                )),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some("parsec".into()),
+                        inter_file_dependencies: false,
+                        workspace_diagnostics: true,
+                        work_done_progress_options: Default::default(),
+                    },
+                )),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic::semantic_tokens_legend(),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            range: None,
+                            ..Default::default()
+                        },
+                    ),
+                ),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 ..Default::default()
             },
-            ..Default::default()
         })
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        let registrations = initial_registrations(
+            &self.state.file_extensions(),
+            self.watched_files_registration.load(Ordering::Relaxed),
+            self.type_hierarchy_registration.load(Ordering::Relaxed),
+        );
+        if !registrations.is_empty()
+            && let Err(e) = self.client.register_capability(registrations).await
+        {
+            warn!("failed to register capabilities: {e}");
+        }
+
+        if let Err(e) = parse::parser_for(&self.state.lang) {
+            self.client
+                .show_message(
+                    MessageType::ERROR,
+                    format!("Parsec: {e:#}. Parsing, symbols and diagnostics are disabled."),
+                )
+                .await;
+        }
+
+        // Indexing starts here rather than in initialize: progress tokens can
+        // only be requested once the client has the initialize response.
+        if self.root_dirs().is_empty() {
+            warn!("No workspace root is provided. Background indexing is disabled.");
+        }
+        self.index_roots("Parsec: Indexing workspace").await;
+        self.state.start_eviction_loop();
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> tower_lsp::jsonrpc::Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri.to_string();
+        let (result_id, diags) = match self.state.docs.get(&uri) {
+            Some(entry) => diagnostics::pull(
+                &entry,
+                &self.state.lang,
+                params.previous_result_id.as_deref(),
+                self.state.duplicate_method_lint(),
+            ),
+            None => {
+                warn!("diagnostic no doc state for {}", uri);
+                return Ok(DocumentDiagnosticReportResult::Report(
+                    DocumentDiagnosticReport::Full(Default::default()),
+                ));
+            }
+        };
+        let report = match diags {
+            Some(items) => DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: Some(result_id),
+                    items,
+                },
+            }),
+            None => DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                    result_id,
+                },
+            }),
+        };
+        Ok(DocumentDiagnosticReportResult::Report(report))
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> tower_lsp::jsonrpc::Result<WorkspaceDiagnosticReportResult> {
+        let previous: HashMap<String, String> = params
+            .previous_result_ids
+            .into_iter()
+            .map(|p| (p.uri.to_string(), p.value))
+            .collect();
+        let uris = self.state.workspace_doc_uris();
+        let mut items = Vec::with_capacity(uris.len());
+        for uri in uris {
+            let Ok(url) = Url::parse(&uri) else {
+                continue;
+            };
+            let Some(entry) = self.state.docs.get(&uri) else {
+                continue;
+            };
+            let (result_id, diags) = diagnostics::pull(
+                &entry,
+                &self.state.lang,
+                previous.get(&uri).map(String::as_str),
+                self.state.duplicate_method_lint(),
+            );
+            let version = self.state.is_open(&uri).then(|| i64::from(entry.version()));
+            drop(entry);
+            items.push(match diags {
+                Some(items) => {
+                    WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                        uri: url,
+                        version,
+                        full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                            result_id: Some(result_id),
+                            items,
+                        },
+                    })
+                }
+                None => WorkspaceDocumentDiagnosticReport::Unchanged(
+                    WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri: url,
+                        version,
+                        unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                            result_id,
+                        },
+                    },
+                ),
+            });
+        }
+        Ok(WorkspaceDiagnosticReportResult::Report(
+            WorkspaceDiagnosticReport { items },
+        ))
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        let config = self.state.config().merge_lsp_value(&params.settings);
+        info!("configuration changed: {config:?}");
+        self.apply_config(config);
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let removed: Vec<Url> = params.event.removed.into_iter().map(|f| f.uri).collect();
+        let added: Vec<Url> = params.event.added.into_iter().map(|f| f.uri).collect();
+        info!(
+            "workspace folders changed: +{} -{}",
+            added.len(),
+            removed.len()
+        );
+        if !removed.is_empty() {
+            self.state.remove_roots(&removed);
+        }
+        if !added.is_empty() {
+            self.state.add_roots(added.clone());
+            let dirs = added.iter().filter_map(|u| u.to_file_path().ok()).collect();
+            self.index_dirs(dirs, "Parsec: Indexing workspace folder")
+                .await;
+        }
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri.to_string();
         let text = params.text_document.text;
         info!("did_open uri={} bytes={}", uri, text.len());
-        self.state.insert_doc(uri.clone(), text.into());
+        self.state.open_doc(uri.clone(), text.into());
+        if let Some(entry) = self.state.docs.get(&uri) {
+            entry.set_version(params.text_document.version);
+        }
+        self.state.set_active_doc(&uri);
         self.state.reindex_doc(&uri);
         self.publish_parse_diagnostics(uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.to_string();
-        if let Some(mut entry) = self.state.docs.get_mut(&uri) {
+        let version = params.text_document.version;
+        if let Some(entry) = self.state.docs.get(&uri) {
+            if version <= entry.version() {
+                warn!(
+                    "did_change stale version uri={} incoming={} stored={}",
+                    uri,
+                    version,
+                    entry.version()
+                );
+                return;
+            }
             for change in params.content_changes {
-                entry.update_text(change.text.into());
+                match change.range {
+                    Some(range) => entry.apply_change(range, &change.text),
+                    None => entry.update_text(change.text.into()),
+                }
             }
+            entry.set_version(version);
         }
-        self.state.reindex_doc(&uri);
+        self.state.set_active_doc(&uri);
+        self.schedule_reindex(uri.clone(), "did_change");
         self.publish_parse_diagnostics(uri).await;
     }
 
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri.to_string();
+        info!("did_close uri={}", uri);
+        self.state.close_doc(&uri);
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri.to_string();
+        match self.state.docs.get(&uri) {
+            Some(entry) => {
+                if let Some(text) = params.text {
+                    entry.update_text(text.into());
+                }
+                entry.force_reparse(&self.state.lang);
+            }
+            None => warn!("did_save no doc state for {}", uri),
+        }
+        self.publish_parse_diagnostics(uri.clone()).await;
+        self.schedule_reindex(uri, "did_save");
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let events: Vec<(Url, FileChangeType)> = params
+            .changes
+            .into_iter()
+            .filter(|change| {
+                change
+                    .uri
+                    .to_file_path()
+                    .is_ok_and(|path| self.state.is_indexable(&path))
+            })
+            .map(|change| (change.uri, change.typ))
+            .collect();
+        if events.is_empty() || !self.state.queue_file_events(events) {
+            return;
+        }
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(state.watch_coalesce()).await;
+            let _ = tokio::task::spawn_blocking(move || state.flush_file_events()).await;
+        });
+    }
+
     async fn document_symbol(
         &self,
         params: DocumentSymbolParams,
     ) -> tower_lsp::jsonrpc::Result<Option<DocumentSymbolResponse>> {
         let uri = params.text_document.uri.to_string();
         let symbols = match self.state.docs.get(&uri) {
-            Some(entry) => {
-                let res = symbols::extract_document_symbols_with_cache(
-                    &entry,
-                    &self.state.lang,
-                    self.state.debounce,
-                );
-                res
-            }
+            Some(entry) => symbols::extract_document_symbols_with_cache(
+                &entry,
+                &self.state.lang,
+                self.state.debounce(),
+            ),
             None => {
                 warn!("document_symbol no doc state for {}", uri);
                 Vec::new()
@@ -92,24 +513,528 @@ impl tower_lsp::LanguageServer for Backend {
         Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
 
+    async fn hover(&self, params: HoverParams) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        Ok(symbols::hover_at(&self.state, &uri, pos))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<GotoDefinitionResponse>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let links = definition::goto(&self.state, &uri, pos);
+        if links.is_empty() {
+            return Ok(None);
+        }
+        if self.definition_links.load(Ordering::Relaxed) {
+            return Ok(Some(GotoDefinitionResponse::Link(links)));
+        }
+        let locations = links
+            .into_iter()
+            .map(|l| Location::new(l.target_uri, l.target_selection_range))
+            .collect();
+        Ok(Some(GotoDefinitionResponse::Array(locations)))
+    }
+
+    async fn goto_implementation(
+        &self,
+        params: request::GotoImplementationParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<request::GotoImplementationResponse>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let links = definition::implementations(&self.state, &uri, pos);
+        if links.is_empty() {
+            return Ok(None);
+        }
+        if self.implementation_links.load(Ordering::Relaxed) {
+            return Ok(Some(request::GotoImplementationResponse::Link(links)));
+        }
+        let locations = links
+            .into_iter()
+            .map(|l| Location::new(l.target_uri, l.target_selection_range))
+            .collect();
+        Ok(Some(request::GotoImplementationResponse::Array(locations)))
+    }
+
+    async fn completion(
+        &self,
+        params: CompletionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let pos = params.text_document_position.position;
+        let items = completion::complete(&self.state, &uri, pos, COMPLETION_LIMIT);
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn references(
+        &self,
+        params: ReferenceParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let pos = params.text_document_position.position;
+        let name = match self.state.docs.get(&uri) {
+            Some(entry) => symbols::identifier_at_position(&entry, &self.state.lang, pos),
+            None => {
+                warn!("references no doc state for {}", uri);
+                None
+            }
+        };
+        let Some((name, _)) = name else {
+            return Ok(None);
+        };
+        let locations = self
+            .state
+            .references
+            .find_references(&name, params.context.include_declaration);
+        Ok(Some(locations))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let highlights = match self.state.docs.get(&uri) {
+            Some(entry) => symbols::highlight_occurrences(&entry, &self.state.lang, pos),
+            None => {
+                warn!("document_highlight no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(highlights))
+    }
+
+    async fn signature_help(
+        &self,
+        params: SignatureHelpParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SignatureHelp>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        if !self.state.docs.contains_key(&uri) {
+            warn!("signature_help no doc state for {}", uri);
+            return Ok(None);
+        }
+        Ok(symbols::signature_help_at(&self.state, &uri, pos))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<PrepareRenameResponse>> {
+        let uri = params.text_document.uri.to_string();
+        match symbols::prepare_rename(&self.state, &uri, params.position) {
+            Ok((placeholder, range)) => Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+                range,
+                placeholder,
+            })),
+            Err(e) => Err(tower_lsp::jsonrpc::Error::invalid_params(e.to_string())),
+        }
+    }
+
+    async fn rename(
+        &self,
+        params: RenameParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let pos = params.text_document_position.position;
+        match symbols::rename_symbol(&self.state, &uri, pos, &params.new_name) {
+            Ok(edit) => Ok(Some(edit)),
+            Err(e) => Err(tower_lsp::jsonrpc::Error::invalid_params(e.to_string())),
+        }
+    }
+
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let actions = match self.state.docs.get(uri.as_str()) {
+            Some(entry) => codeactions::collect(
+                &entry,
+                &self.state.lang,
+                &uri,
+                params.range,
+                params.context.only.as_deref(),
+            ),
+            None => {
+                warn!("code_action no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(actions))
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TypeHierarchyItem>>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let items = typehierarchy::prepare(&self.state, &uri, pos);
+        Ok((!items.is_empty()).then_some(items))
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(typehierarchy::supertypes(&self.state, &params.item)))
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(typehierarchy::subtypes(&self.state, &params.item)))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<LinkedEditingRanges>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let ranges = match self.state.docs.get(&uri) {
+            Some(entry) => symbols::linked_editing_ranges(&entry, &self.state.lang, pos),
+            None => {
+                warn!("linked_editing_range no doc state for {}", uri);
+                None
+            }
+        };
+        Ok(ranges)
+    }
+
+    async fn code_lens(
+        &self,
+        params: CodeLensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let lenses = match self.state.docs.get(uri.as_str()) {
+            Some(entry) => codelens::collect(&entry, &self.state.lang, &uri),
+            None => {
+                warn!("code_lens no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(lenses))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            REINDEX_COMMAND => {
+                if self.reindexing.swap(true, Ordering::AcqRel) {
+                    self.client
+                        .show_message(
+                            MessageType::WARNING,
+                            "Parsec: a workspace reindex is already running",
+                        )
+                        .await;
+                    return Ok(None);
+                }
+                self.state.reset_index();
+                let runs = self.index_roots("Parsec: Reindexing workspace").await;
+                let reindexing = self.reindexing.clone();
+                tokio::spawn(async move {
+                    for run in runs {
+                        let _ = run.await;
+                    }
+                    reindexing.store(false, Ordering::Release);
+                });
+                Ok(None)
+            }
+            CLEAR_CACHE_COMMAND => {
+                let mut removed = 0usize;
+                for root in self.root_dirs() {
+                    match cache::clear(&root) {
+                        Ok(true) => removed += 1,
+                        Ok(false) => {}
+                        Err(e) => warn!("failed to clear symbol cache: {e:#}"),
+                    }
+                }
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!("Parsec: removed {removed} symbol cache file(s)"),
+                    )
+                    .await;
+                Ok(None)
+            }
+            INDEX_STATS_COMMAND => {
+                let stats = self.state.symbols.stats();
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!(
+                            "Parsec: {} symbols indexed across {} files",
+                            stats.symbols, stats.documents
+                        ),
+                    )
+                    .await;
+                Ok(None)
+            }
+            // The same numbers as a result, for attaching to bug reports.
+            INDEX_STATS_JSON_COMMAND => {
+                let stats = self.state.symbols.stats();
+                let roots: Vec<String> = self.state.roots().iter().map(|r| r.to_string()).collect();
+                Ok(Some(serde_json::json!({
+                    "documents": stats.documents,
+                    "symbols": stats.symbols,
+                    "symbolsByKind": stats.by_kind,
+                    "roots": roots,
+                })))
+            }
+            codelens::RUN_TESTSET_COMMAND => {
+                // Placeholder until there is a test runner to hand off to.
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        "Run: julia --project -e 'using Pkg; Pkg.test()'",
+                    )
+                    .await;
+                Ok(None)
+            }
+            other => Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "unknown command `{other}`"
+            ))),
+        }
+    }
+
+    async fn document_link(
+        &self,
+        params: DocumentLinkParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<DocumentLink>>> {
+        let uri = params.text_document.uri;
+        let links = match self.state.docs.get(uri.as_str()) {
+            Some(entry) => links::collect(&entry, &self.state.lang, &uri),
+            None => {
+                warn!("document_link no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(links))
+    }
+
+    async fn folding_range(
+        &self,
+        params: FoldingRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<FoldingRange>>> {
+        let uri = params.text_document.uri.to_string();
+        let ranges = match self.state.docs.get(&uri) {
+            Some(entry) => symbols::folding_ranges(&entry, &self.state.lang),
+            None => {
+                warn!("folding_range no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(ranges))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri.to_string();
+        let ranges = match self.state.docs.get(&uri) {
+            Some(entry) => {
+                symbols::selection_ranges_at(&entry, &self.state.lang, &params.positions)
+            }
+            None => {
+                warn!("selection_range no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(ranges))
+    }
+
+    async fn formatting(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+        let Some(entry) = self.state.docs.get(&uri) else {
+            warn!("formatting no doc state for {}", uri);
+            return Ok(None);
+        };
+        let text = entry.text();
+        drop(entry);
+        Ok(Some(formatter::format_source(&text, params.options)))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+        let Some(entry) = self.state.docs.get(&uri) else {
+            warn!("range_formatting no doc state for {}", uri);
+            return Ok(None);
+        };
+        let text = entry.text();
+        drop(entry);
+        Ok(Some(formatter::format_range(
+            &text,
+            params.range,
+            params.options,
+        )))
+    }
+
+    async fn inlay_hint(
+        &self,
+        params: InlayHintParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<InlayHint>>> {
+        if !self.state.inlay_hints() {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri.to_string();
+        let hints = match self.state.docs.get(&uri) {
+            Some(entry) => {
+                hints::collect(&entry, &self.state.lang, params.range, &self.state.symbols)
+            }
+            None => {
+                warn!("inlay_hint no doc state for {}", uri);
+                Vec::new()
+            }
+        };
+        Ok(Some(hints))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+        let pos = params.text_document_position_params.position;
+        let items = callhierarchy::prepare(&self.state, &uri, pos);
+        Ok((!items.is_empty()).then_some(items))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        Ok(Some(callhierarchy::incoming(&self.state, &params.item)))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        Ok(Some(callhierarchy::outgoing(&self.state, &params.item)))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri.to_string();
+        let tokens = match self.state.docs.get(&uri) {
+            Some(entry) => semantic::full(&entry, &self.state.lang),
+            None => {
+                warn!("semantic_tokens_full no doc state for {}", uri);
+                SemanticTokens::default()
+            }
+        };
+        Ok(Some(SemanticTokensResult::Tokens(tokens)))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = params.text_document.uri.to_string();
+        let result = match self.state.docs.get(&uri) {
+            Some(entry) => semantic::delta(&entry, &self.state.lang, &params.previous_result_id),
+            None => {
+                warn!("semantic_tokens_full_delta no doc state for {}", uri);
+                SemanticTokensFullDeltaResult::Tokens(SemanticTokens::default())
+            }
+        };
+        Ok(Some(result))
+    }
+
     async fn symbol(
         &self,
         params: WorkspaceSymbolParams,
     ) -> tower_lsp::jsonrpc::Result<Option<Vec<SymbolInformation>>> {
         let t0 = Instant::now();
 
-        let q = params.query.clone();
-        let limit = 2000usize;
-        let root = self.state.root_path();
-        let root_filter = if q.is_empty() || q.len() <= 2 {
-            root.as_deref()
+        let (kinds, rest) = split_kind_filter(&params.query);
+        let (file_glob, container, q) = split_scope_filters(rest);
+        let roots = self.state.roots();
+        // Short queries are limited to the workspace folders. Without any
+        // folder (a single file opened on its own) the filter is empty and
+        // every indexed symbol is eligible.
+        let root_filter: &[Url] = if q.is_empty() || q.len() <= 2 {
+            &roots
         } else {
-            None
+            &[]
+        };
+        let filter = SymbolFilter {
+            roots: root_filter,
+            kinds,
+            file_glob,
+            container,
         };
 
-        let results = self.state.symbols.search_fuzzy(&q, root_filter, limit);
+        let limit = self.state.config().max_workspace_symbols;
+        let entries = if q.is_empty()
+            && !kinds.is_empty()
+            && filter.file_glob.is_none()
+            && filter.container.is_none()
+        {
+            symbols_of_kinds(&self.state.symbols, kinds, root_filter, limit)
+        } else {
+            let active = self.state.active_doc();
+            self.state
+                .symbols
+                .search_with_filter(&q, &filter, active.as_deref(), limit)
+        };
+        let results: Vec<SymbolInformation> = entries
+            .iter()
+            .map(SymbolEntry::to_symbol_information)
+            .collect();
         tracing::info!(
-            "Workspace Symbol Request: Query='{q}' Count={} Time={:?}",
+            "Workspace Symbol Request: Query='{}' Count={} Time={:?}",
+            params.query,
             results.len(),
             t0.elapsed()
         );
@@ -123,10 +1048,78 @@ impl tower_lsp::LanguageServer for Backend {
 }
 
 impl Backend {
+    // Exclude patterns only take effect on the next indexer run.
+    fn apply_config(&self, config: ParsecConfig) {
+        let level = config.log_level.clone();
+        let previous = self.state.update_config(config);
+        if previous.log_level != level {
+            match EnvFilter::try_new(&level) {
+                Ok(filter) => {
+                    if let Err(e) = self.log_filter.reload(filter) {
+                        warn!("failed to apply log level {level:?}: {e}");
+                    }
+                }
+                Err(e) => warn!("ignoring log level {level:?}: {e}"),
+            }
+        }
+    }
+
+    // Typing or saving in bursts reindexes the document once, a debounce
+    // after the last event, by which time the parse is no longer deferred
+    // and the index sees the latest text.
+    fn schedule_reindex(&self, uri: String, reason: &'static str) {
+        let marked_at = self.state.mark_dirty(&uri);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(state.debounce()).await;
+            if state.take_pending_reindex(&uri, marked_at) {
+                info!("{reason} reindex uri={}", uri);
+                state.reindex_doc(&uri);
+            }
+        });
+    }
+
+    fn root_dirs(&self) -> Vec<std::path::PathBuf> {
+        self.state
+            .roots()
+            .iter()
+            .filter_map(|u| u.to_file_path().ok())
+            .collect()
+    }
+
+    // Progress for each root ends with a $/progress end notification, or a
+    // showMessage when the client cannot display progress.
+    async fn index_roots(&self, title: &str) -> Vec<tokio::task::JoinHandle<()>> {
+        self.index_dirs(self.root_dirs(), title).await
+    }
+
+    async fn index_dirs(
+        &self,
+        dirs: Vec<std::path::PathBuf>,
+        title: &str,
+    ) -> Vec<tokio::task::JoinHandle<()>> {
+        let supported = self.work_done_progress.load(Ordering::Relaxed);
+        let mut runs = Vec::new();
+        for root_dir in dirs {
+            let progress =
+                progress::WorkspaceProgressReporter::begin(self.client.clone(), supported, title)
+                    .await;
+            runs.push(self.state.start_indexer(root_dir, Some(progress)));
+        }
+        runs
+    }
+
     async fn publish_parse_diagnostics(&self, uri: String) {
+        if self.pull_diagnostics.load(Ordering::Relaxed) {
+            return;
+        }
         use diagnostics::simple_syntax_error_diag;
-        let text = match self.state.docs.get(&uri) {
-            Some(d) => d.text(),
+        // Reuses the document's own tree, which a save has just rebuilt.
+        let (tree, text) = match self.state.docs.get(&uri) {
+            Some(d) => {
+                d.parse_with_debounce(&self.state.lang, Duration::ZERO);
+                d.parsed_snapshot()
+            }
             None => {
                 self.client
                     .log_message(MessageType::WARNING, "no doc state for diagnostics")
@@ -134,45 +1127,430 @@ impl Backend {
                 return;
             }
         };
-        let diags = match parse::parse(&text, None) {
-            Ok(_) => Vec::new(),
-            Err(e) => vec![simple_syntax_error_diag(&format!("parse error: {e}"), 0, 0)],
-        };
         let uri = Url::parse(&uri).unwrap();
+        let diags = match tree {
+            Some(tree) => {
+                let mut diags = diagnostics::diagnostics_from_tree(&tree, &text);
+                if self.state.duplicate_method_lint() {
+                    diags.extend(diagnostics::duplicate_method_diagnostics(
+                        &tree, &text, &uri,
+                    ));
+                }
+                diags
+            }
+            None => vec![simple_syntax_error_diag("parse error: no tree", 0, 0)],
+        };
         self.client.publish_diagnostics(uri, diags, None).await;
     }
 }
 
-fn workspace_root_from_params(params: &InitializeParams) -> Option<PathBuf> {
-    if let Some(folders) = &params.workspace_folders {
-        if let Some(first) = folders.first() {
-            return first.uri.to_file_path().ok();
+fn initial_registrations(
+    extensions: &[String],
+    watched_files: bool,
+    type_hierarchy: bool,
+) -> Vec<Registration> {
+    let mut registrations = Vec::new();
+    if watched_files {
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: extensions
+                .iter()
+                .map(|ext| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(format!("**/*.{ext}")),
+                    kind: Some(WatchKind::Create | WatchKind::Change | WatchKind::Delete),
+                })
+                .collect(),
+        };
+        registrations.push(Registration {
+            id: "parsec-watch-jl".into(),
+            method: "workspace/didChangeWatchedFiles".into(),
+            register_options: serde_json::to_value(options).ok(),
+        });
+    }
+    if type_hierarchy {
+        registrations.push(Registration {
+            id: "parsec-type-hierarchy".into(),
+            method: "textDocument/prepareTypeHierarchy".into(),
+            register_options: Some(serde_json::json!({
+                "documentSelector": [{ "language": "julia" }],
+            })),
+        });
+    }
+    registrations
+}
+
+fn workspace_roots_from_params(params: &InitializeParams) -> Vec<Url> {
+    if let Some(folders) = &params.workspace_folders
+        && !folders.is_empty()
+    {
+        return folders.iter().map(|f| f.uri.clone()).collect();
+    }
+    params.root_uri.iter().cloned().collect()
+}
+
+// A leading `f:`, `t:`, `m:` or `c:` limits a workspace symbol query to
+// functions, types, modules or constants; see the README.
+fn split_kind_filter(query: &str) -> (&'static [SymbolKind], &str) {
+    const FUNCTIONS: &[SymbolKind] = &[
+        SymbolKind::FUNCTION,
+        SymbolKind::METHOD,
+        SymbolKind::INTERFACE,
+    ];
+    const TYPES: &[SymbolKind] = &[
+        SymbolKind::STRUCT,
+        SymbolKind::CLASS,
+        SymbolKind::ENUM,
+        SymbolKind::TYPE_PARAMETER,
+    ];
+    const MODULES: &[SymbolKind] = &[SymbolKind::MODULE];
+    const CONSTANTS: &[SymbolKind] = &[SymbolKind::CONSTANT, SymbolKind::ENUM_MEMBER];
+    let query = query.trim_start();
+    let kinds = match query.get(..2) {
+        Some("f:") => FUNCTIONS,
+        Some("t:") => TYPES,
+        Some("m:") => MODULES,
+        Some("c:") => CONSTANTS,
+        _ => return (&[], query),
+    };
+    (kinds, query[2..].trim_start())
+}
+
+// A prefix on its own, such as `t:`, lists the symbols of its kinds sorted
+// by name rather than in whatever order the index holds them.
+fn symbols_of_kinds(
+    symbols: &index::SymbolIndex,
+    kinds: &[SymbolKind],
+    roots: &[Url],
+    limit: usize,
+) -> Vec<SymbolEntry> {
+    let mut out: Vec<SymbolEntry> = kinds
+        .iter()
+        .flat_map(|&kind| symbols.search_by_kind(kind, roots))
+        .collect();
+    out.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.uri.cmp(&b.uri)));
+    out.truncate(limit);
+    out
+}
+
+// Pulls `path:<glob>` and `in:<container>` words out of a workspace symbol
+// query. A glob without a leading `/` or `*` matches at any depth, so
+// `path:test/*.jl` finds every `test` directory. An invalid glob is dropped
+// rather than hiding every result while it is still being typed.
+fn split_scope_filters(query: &str) -> (Option<GlobMatcher>, Option<&str>, String) {
+    let mut file_glob = None;
+    let mut container = None;
+    let mut rest = Vec::new();
+    for word in query.split_whitespace() {
+        if let Some(pattern) = word.strip_prefix("path:") {
+            let pattern = if pattern.starts_with(['/', '*']) {
+                pattern.to_string()
+            } else {
+                format!("**/{pattern}")
+            };
+            match GlobBuilder::new(&pattern).literal_separator(true).build() {
+                Ok(glob) => file_glob = Some(glob.compile_matcher()),
+                Err(e) => debug!("ignoring symbol path filter {pattern:?}: {e}"),
+            }
+        } else if let Some(name) = word.strip_prefix("in:") {
+            container = Some(name);
+        } else {
+            rest.push(word);
         }
     }
-    if let Some(root_uri) = &params.root_uri {
-        return root_uri.to_file_path().ok();
+    (file_glob, container, rest.join(" "))
+}
+
+#[derive(Parser)]
+#[command(name = "parsec", version, about = "Julia language server")]
+struct Cli {
+    /// Communicate over stdin/stdout (the default; accepted for client compatibility).
+    #[arg(long, conflicts_with_all = ["pipe", "socket"])]
+    stdio: bool,
+    /// Connect to the client's named pipe (Windows) or Unix domain socket.
+    #[arg(long, value_name = "PATH", conflicts_with = "socket")]
+    pipe: Option<std::path::PathBuf>,
+    /// Connect to the client on this TCP port of localhost.
+    #[arg(long, value_name = "PORT")]
+    socket: Option<u16>,
+    /// Do not read or write the on-disk symbol cache.
+    #[arg(long)]
+    no_cache: bool,
+    /// Write the log here instead of a daily file in $PARSEC_LOG_DIR or the user cache directory.
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+    /// Initial log filter, e.g. "debug" or "parsec=trace"; overrides RUST_LOG.
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+}
+
+const LOG_DIR_ENV: &str = "PARSEC_LOG_DIR";
+
+// $XDG_CACHE_HOME/parsec on Linux and the platform cache directory
+// elsewhere, falling back to the temp directory.
+fn default_log_dir() -> std::path::PathBuf {
+    dirs::cache_dir()
+        .map(|d| d.join("parsec"))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+// --log-file wins over PARSEC_LOG_DIR, which wins over the default daily
+// file. PARSEC_LOG_DIR=off logs to stderr instead of any file.
+fn log_writer(cli: &Cli) -> BoxMakeWriter {
+    if let Some(path) = &cli.log_file {
+        let dir = path
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+        let name = path
+            .file_name()
+            .map_or("parsec.log".into(), |n| n.to_string_lossy());
+        return file_writer(dir, &name, Rotation::NEVER);
+    }
+    let dir = match std::env::var_os(LOG_DIR_ENV) {
+        Some(dir) if dir == "off" => return BoxMakeWriter::new(std::io::stderr),
+        Some(dir) if !dir.is_empty() => std::path::PathBuf::from(dir),
+        _ => default_log_dir(),
+    };
+    file_writer(&dir, "parsec.log", Rotation::DAILY)
+}
+
+// A directory that cannot be created or written falls back to one under the
+// temp dir, and failing that to stderr, rather than stopping the server.
+fn file_writer(dir: &std::path::Path, name: &str, rotation: Rotation) -> BoxMakeWriter {
+    let open = |dir: &std::path::Path| {
+        RollingFileAppender::builder()
+            .rotation(rotation.clone())
+            .filename_prefix(name)
+            .build(dir)
+    };
+    let err = match open(dir) {
+        Ok(appender) => return BoxMakeWriter::new(appender),
+        Err(e) => e,
+    };
+    let fallback = std::env::temp_dir().join("parsec");
+    match open(&fallback) {
+        Ok(appender) => {
+            eprintln!(
+                "parsec: cannot log to {}: {err}; logging to {} instead",
+                dir.display(),
+                fallback.display()
+            );
+            BoxMakeWriter::new(appender)
+        }
+        Err(_) => {
+            eprintln!(
+                "parsec: cannot log to {}: {err}; logging to stderr instead",
+                dir.display()
+            );
+            BoxMakeWriter::new(std::io::stderr)
+        }
     }
-    None
 }
 
 #[tokio::main]
 async fn main() {
-    let file_appender = rolling::daily("/tmp", "parsec.log");
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_writer(file_appender)
+    let cli = Cli::parse();
+    let file_appender = log_writer(&cli);
+    let filter = match &cli.log_level {
+        Some(level) => EnvFilter::try_new(level).unwrap_or_else(|e| {
+            eprintln!("parsec: ignoring --log-level {level:?}: {e}");
+            EnvFilter::new("info")
+        }),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    // Behind a reload layer so logLevel can change it at runtime.
+    let (filter, log_filter) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(file_appender))
         .init();
     info!(
         "boot pid={} argv0={}",
         std::process::id(),
         std::env::args().next().unwrap_or_default()
     );
-    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
     let state = Arc::new(ServerState::default());
+    state.set_use_cache(!cli.no_cache);
     let (service, socket) = LspService::new(|client| Backend {
         client,
         state: state.clone(),
+        log_filter: log_filter.clone(),
+        reindexing: Arc::new(AtomicBool::new(false)),
+        work_done_progress: AtomicBool::new(false),
+        pull_diagnostics: AtomicBool::new(false),
+        definition_links: AtomicBool::new(false),
+        implementation_links: AtomicBool::new(false),
+        type_hierarchy_registration: AtomicBool::new(false),
+        watched_files_registration: AtomicBool::new(false),
     });
-    Server::new(stdin, stdout, socket).serve(service).await;
+
+    // For pipe and socket transports the client is the one listening.
+    if let Some(port) = cli.socket {
+        match tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(stream) => {
+                let (read, write) = tokio::io::split(stream);
+                Server::new(read, write, socket).serve(service).await;
+            }
+            Err(e) => {
+                error!("failed to connect to port {port}: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(path) = &cli.pipe {
+        match connect_pipe(path).await {
+            Ok((read, write)) => Server::new(read, write, socket).serve(service).await,
+            Err(e) => {
+                error!("failed to connect to pipe {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
+        Server::new(stdin, stdout, socket).serve(service).await;
+    }
+}
+
+#[cfg(unix)]
+async fn connect_pipe(
+    path: &std::path::Path,
+) -> std::io::Result<(
+    impl tokio::io::AsyncRead + Unpin,
+    impl tokio::io::AsyncWrite + Unpin,
+)> {
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    Ok(stream.into_split())
+}
+
+#[cfg(windows)]
+async fn connect_pipe(
+    path: &std::path::Path,
+) -> std::io::Result<(
+    impl tokio::io::AsyncRead + Unpin,
+    impl tokio::io::AsyncWrite + Unpin,
+)> {
+    let pipe = tokio::net::windows::named_pipe::ClientOptions::new().open(path)?;
+    Ok(tokio::io::split(pipe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_watcher_needs_dynamic_registration() {
+        let extensions = ["jl".to_string(), "jmd".to_string()];
+        assert!(initial_registrations(&extensions, false, false).is_empty());
+        let methods: Vec<String> = initial_registrations(&extensions, true, true)
+            .into_iter()
+            .map(|r| r.method)
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                "workspace/didChangeWatchedFiles",
+                "textDocument/prepareTypeHierarchy"
+            ]
+        );
+        let watcher = &initial_registrations(&extensions, true, false)[0];
+        let options = watcher.register_options.as_ref().unwrap();
+        assert_eq!(options["watchers"][1]["globPattern"], "**/*.jmd");
+    }
+
+    #[test]
+    fn unwritable_log_dir_falls_back() {
+        use tracing_subscriber::fmt::MakeWriter;
+        let blocker = std::env::temp_dir().join(format!("parsec-log-{}", std::process::id()));
+        std::fs::write(&blocker, "").unwrap();
+        // A directory below a regular file can never be created.
+        let dir = blocker.join("logs");
+        let writer = file_writer(&dir, "parsec.log", Rotation::NEVER);
+        let mut out = writer.make_writer();
+        std::io::Write::write_all(&mut out, b"still logging\n").unwrap();
+        assert!(!dir.exists());
+        std::fs::remove_file(&blocker).unwrap();
+    }
+
+    #[test]
+    fn kind_prefix_limits_workspace_symbols() {
+        assert_eq!(
+            split_kind_filter("  f: area"),
+            (
+                &[
+                    SymbolKind::FUNCTION,
+                    SymbolKind::METHOD,
+                    SymbolKind::INTERFACE
+                ][..],
+                "area"
+            )
+        );
+        assert_eq!(split_kind_filter("m:Plots").1, "Plots");
+        assert_eq!(
+            split_kind_filter("c:").0,
+            &[SymbolKind::CONSTANT, SymbolKind::ENUM_MEMBER]
+        );
+        assert_eq!(split_kind_filter("x:Anim"), (&[][..], "x:Anim"));
+
+        let index = index::SymbolIndex::default();
+        let uri = Url::parse("file:///ws/a.jl").unwrap();
+        let entry = |name: &str, kind| {
+            SymbolEntry::new(
+                name.to_string(),
+                uri.clone(),
+                std::path::PathBuf::from("/ws/a.jl"),
+                Range::default(),
+                kind,
+                None,
+            )
+        };
+        index.insert_entries(
+            &uri,
+            vec![
+                entry("Animation", SymbolKind::CLASS),
+                entry("animate", SymbolKind::FUNCTION),
+            ],
+        );
+        let (kinds, query) = split_kind_filter("t:Anim");
+        let filter = SymbolFilter {
+            roots: &[],
+            kinds,
+            file_glob: None,
+            container: None,
+        };
+        let found = index.search_with_filter(query, &filter, None, 10);
+        let names: Vec<&str> = found.iter().map(|e| e.name.as_ref()).collect();
+        assert_eq!(names, ["Animation"]);
+    }
+
+    #[test]
+    fn bare_kind_prefix_lists_symbols_by_name() {
+        let index = index::SymbolIndex::default();
+        let uri = Url::parse("file:///ws/a.jl").unwrap();
+        let entry = |name: &str, kind| {
+            SymbolEntry::new(
+                name.to_string(),
+                uri.clone(),
+                std::path::PathBuf::from("/ws/a.jl"),
+                Range::default(),
+                kind,
+                None,
+            )
+        };
+        index.insert_entries(
+            &uri,
+            vec![
+                entry("Zebra", SymbolKind::STRUCT),
+                entry("draw", SymbolKind::FUNCTION),
+                entry("Animal", SymbolKind::CLASS),
+                entry("Color", SymbolKind::ENUM),
+            ],
+        );
+        let (kinds, _) = split_kind_filter("t:");
+        let names = |limit| -> Vec<String> {
+            symbols_of_kinds(&index, kinds, &[], limit)
+                .iter()
+                .map(|e| e.name.to_string())
+                .collect()
+        };
+        assert_eq!(names(10), ["Animal", "Color", "Zebra"]);
+        assert_eq!(names(2), ["Animal", "Color"]);
+    }
 }
@@ -1,16 +1,27 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use tree_sitter::{Language, Parser, Tree};
 
 pub fn julia_lang() -> Language {
     tree_sitter_julia::LANGUAGE.into()
 }
 
-pub fn parse(source: &str, old: Option<&Tree>) -> Result<Tree> {
+// set_language is where a grammar built against another tree-sitter ABI is
+// rejected, so this fails the same way for every parse.
+pub fn parser_for(lang: &Language) -> Result<Parser> {
     let mut parser = Parser::new();
-    parser.set_language(&julia_lang())?;
+    parser.set_language(lang).with_context(|| {
+        format!(
+            "the bundled Julia grammar (ABI {}) is not supported by this tree-sitter build",
+            lang.abi_version()
+        )
+    })?;
+    Ok(parser)
+}
+
+pub fn parse(source: &str, old: Option<&Tree>) -> Result<Tree> {
+    let mut parser = parser_for(&julia_lang())?;
     let tree = parser
         .parse(source, old)
         .ok_or_else(|| anyhow::anyhow!("parser returned None"))?;
     Ok(tree)
 }
-
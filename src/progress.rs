@@ -0,0 +1,129 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tower_lsp::Client;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    MessageType, NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
+};
+use tracing::warn;
+
+const REPORT_EVERY_FILES: usize = 50;
+const REPORT_EVERY: Duration = Duration::from_millis(500);
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+// Reports indexing progress to the client. The indexer calls file_done from
+// rayon workers, so notifications go through a channel drained by a single
+// task, which keeps begin/report/end in order.
+pub struct WorkspaceProgressReporter {
+    client: Client,
+    tx: Option<mpsc::UnboundedSender<WorkDoneProgress>>,
+    total: AtomicUsize,
+    done: AtomicUsize,
+    last_report: Mutex<(Instant, usize)>,
+}
+
+impl WorkspaceProgressReporter {
+    // Without client support for window.workDoneProgress nothing is sent
+    // until finish, which falls back to a single window/showMessage.
+    pub async fn begin(client: Client, supported: bool, title: &str) -> Arc<Self> {
+        let tx = if supported {
+            Self::create(&client, title).await
+        } else {
+            None
+        };
+        Arc::new(Self {
+            client,
+            tx,
+            total: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            last_report: Mutex::new((Instant::now(), 0)),
+        })
+    }
+
+    async fn create(
+        client: &Client,
+        title: &str,
+    ) -> Option<mpsc::UnboundedSender<WorkDoneProgress>> {
+        let token = NumberOrString::String(format!(
+            "parsec/indexing/{}",
+            NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+        ));
+        let created = client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await;
+        if let Err(e) = created {
+            warn!("client refused progress token: {e}");
+            return None;
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<WorkDoneProgress>();
+        let forward = client.clone();
+        tokio::spawn(async move {
+            while let Some(value) = rx.recv().await {
+                forward
+                    .send_notification::<Progress>(ProgressParams {
+                        token: token.clone(),
+                        value: ProgressParamsValue::WorkDone(value),
+                    })
+                    .await;
+            }
+        });
+        let _ = tx.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            cancellable: Some(false),
+            message: None,
+            percentage: Some(0),
+        }));
+        Some(tx)
+    }
+
+    pub fn add_total(&self, files: usize) {
+        self.total.fetch_add(files, Ordering::Relaxed);
+    }
+
+    pub fn file_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        {
+            let mut last = self.last_report.lock();
+            if done.saturating_sub(last.1) < REPORT_EVERY_FILES && last.0.elapsed() < REPORT_EVERY {
+                return;
+            }
+            *last = (Instant::now(), done);
+        }
+        let total = self.total.load(Ordering::Relaxed).max(done);
+        let _ = tx.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(format!("Indexed {done}/{total} files")),
+            percentage: Some((done * 100 / total) as u32),
+        }));
+    }
+
+    pub async fn finish(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        let message = format!("Indexed {done} files");
+        match &self.tx {
+            Some(tx) => {
+                let _ = tx.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(message),
+                }));
+            }
+            None => {
+                self.client
+                    .show_message(MessageType::INFO, format!("Parsec: {message}"))
+                    .await;
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensEdit, SemanticTokensFullDeltaResult, SemanticTokensLegend, SymbolKind,
+};
+use tree_sitter::{Node, TreeCursor};
+
+use crate::line_index::LineIndex;
+use crate::state::DocState;
+use crate::symbols::{collect_named_descendants_by, kind_for_node, name_node};
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::STRING,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::OPERATOR,
+];
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[SemanticTokenModifier::DECLARATION];
+
+const MOD_DECLARATION: u32 = 1 << 0;
+
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn token_type_index(ty: &SemanticTokenType) -> u32 {
+    TOKEN_TYPES.iter().position(|t| t == ty).unwrap_or(0) as u32
+}
+
+pub fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+fn is_keyword(node_type: &str) -> bool {
+    matches!(
+        node_type,
+        "function"
+            | "end"
+            | "module"
+            | "baremodule"
+            | "struct"
+            | "mutable"
+            | "abstract"
+            | "primitive"
+            | "type"
+            | "macro"
+            | "if"
+            | "elseif"
+            | "else"
+            | "for"
+            | "while"
+            | "begin"
+            | "let"
+            | "quote"
+            | "return"
+            | "try"
+            | "catch"
+            | "finally"
+            | "do"
+            | "const"
+            | "global"
+            | "local"
+            | "import"
+            | "using"
+            | "export"
+            | "public"
+            | "where"
+            | "break_statement"
+            | "continue_statement"
+    )
+}
+
+fn definition_token_type(def: Node) -> SemanticTokenType {
+    match kind_for_node(def) {
+        Some(SymbolKind::FUNCTION) => SemanticTokenType::FUNCTION,
+        Some(SymbolKind::MODULE) => SemanticTokenType::NAMESPACE,
+        Some(SymbolKind::CONSTANT) => SemanticTokenType::VARIABLE,
+        _ => SemanticTokenType::TYPE,
+    }
+}
+
+fn is_type_position(node: &Node, text: &str) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let is_first = parent.named_child(0).map(|c| c.id()) == Some(node.id());
+    match parent.kind() {
+        "typed_expression" | "where_expression" => !is_first,
+        "unary_typed_expression" | "curly_expression" | "parametrized_type_expression" => true,
+        "binary_expression" => {
+            !is_first
+                && parent
+                    .child(1)
+                    .is_some_and(|op| &text[op.start_byte()..op.end_byte()] == "<:")
+        }
+        _ => false,
+    }
+}
+
+pub fn full(doc: &DocState, lang: &tree_sitter::Language) -> SemanticTokens {
+    let data = semantic_tokens(doc, lang);
+    let result_id = next_result_id();
+    doc.swap_tokens(result_id.clone(), data.clone());
+    SemanticTokens {
+        result_id: Some(result_id),
+        data,
+    }
+}
+
+// Sends the change against `previous` as one edit spanning everything
+// between the unchanged head and tail of the token list, which is what a
+// single keystroke produces. A `previous` that is not the last result sent
+// gets the full list instead.
+pub fn delta(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    previous: &str,
+) -> SemanticTokensFullDeltaResult {
+    let data = semantic_tokens(doc, lang);
+    let result_id = next_result_id();
+    let Some((_, old)) = doc
+        .swap_tokens(result_id.clone(), data.clone())
+        .filter(|(id, _)| id == previous)
+    else {
+        return SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+            result_id: Some(result_id),
+            data,
+        });
+    };
+    let head = old.iter().zip(&data).take_while(|(a, b)| a == b).count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(data[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let inserted = &data[head..data.len() - tail];
+    let deleted = old.len() - head - tail;
+    let edits = if deleted == 0 && inserted.is_empty() {
+        Vec::new()
+    } else {
+        // Offsets count the integers of the encoded array, five per token.
+        vec![SemanticTokensEdit {
+            start: (head * 5) as u32,
+            delete_count: (deleted * 5) as u32,
+            data: Some(inserted.to_vec()),
+        }]
+    };
+    SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+        result_id: Some(result_id),
+        edits,
+    })
+}
+
+fn next_result_id() -> String {
+    NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
+fn semantic_tokens(doc: &DocState, lang: &tree_sitter::Language) -> Vec<SemanticToken> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let root = tree.root_node();
+
+    let definition_names: HashMap<usize, SemanticTokenType> =
+        collect_named_descendants_by(root, &|n: &Node| kind_for_node(*n).is_some())
+            .into_iter()
+            .filter_map(|def| name_node(def).map(|n| (n.id(), definition_token_type(def))))
+            .collect();
+
+    let mut raw: Vec<(usize, usize, u32, u32)> = Vec::new();
+    let mut cursor = tree.walk();
+    collect_semantic_tokens(&text, &mut cursor, &definition_names, &mut raw);
+
+    let mut out = Vec::with_capacity(raw.len());
+    let mut prev_line = 0u32;
+    let mut prev_start = 0u32;
+    for (start, end, token_type, modifiers) in raw {
+        let first = idx.to_pos(start).line as usize;
+        let last = idx.to_pos(end).line as usize;
+        for line in first..=last {
+            let seg_start = start.max(idx.line_start(line));
+            let seg_end = end.min(idx.line_end(line));
+            if seg_end <= seg_start {
+                continue;
+            }
+            let pos = idx.to_pos(seg_start);
+            let delta_line = pos.line - prev_line;
+            let delta_start = if delta_line == 0 {
+                pos.character - prev_start
+            } else {
+                pos.character
+            };
+            out.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length: idx.to_pos(seg_end).character - pos.character,
+                token_type,
+                token_modifiers_bitset: modifiers,
+            });
+            prev_line = pos.line;
+            prev_start = pos.character;
+        }
+    }
+    out
+}
+
+fn collect_semantic_tokens(
+    text: &str,
+    cursor: &mut TreeCursor,
+    definition_names: &HashMap<usize, SemanticTokenType>,
+    out: &mut Vec<(usize, usize, u32, u32)>,
+) {
+    loop {
+        let node = cursor.node();
+        let token = if let Some(ty) = definition_names.get(&node.id()) {
+            Some((token_type_index(ty), MOD_DECLARATION))
+        } else {
+            match node.kind() {
+                "macro_identifier" => Some((token_type_index(&SemanticTokenType::MACRO), 0)),
+                "string_literal"
+                | "prefixed_string_literal"
+                | "character_literal"
+                | "command_literal" => Some((token_type_index(&SemanticTokenType::STRING), 0)),
+                "line_comment" | "block_comment" => {
+                    Some((token_type_index(&SemanticTokenType::COMMENT), 0))
+                }
+                "integer_literal" | "float_literal" => {
+                    Some((token_type_index(&SemanticTokenType::NUMBER), 0))
+                }
+                "identifier" if is_type_position(&node, text) => {
+                    Some((token_type_index(&SemanticTokenType::TYPE), 0))
+                }
+                "operator" => Some((token_type_index(&SemanticTokenType::OPERATOR), 0)),
+                "boolean_literal" => Some((token_type_index(&SemanticTokenType::KEYWORD), 0)),
+                k if is_keyword(k) => Some((token_type_index(&SemanticTokenType::KEYWORD), 0)),
+                _ => None,
+            }
+        };
+        if let Some((token_type, modifiers)) = token {
+            out.push((node.start_byte(), node.end_byte(), token_type, modifiers));
+        } else if cursor.goto_first_child() {
+            collect_semantic_tokens(text, cursor, definition_names, out);
+            cursor.goto_parent();
+        }
+        if !cursor.goto_next_sibling() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ServerState;
+
+    const URI: &str = "file:///t.jl";
+
+    // Sends `before` in full, then asks for a delta after the text became
+    // `after`.
+    fn delta_after(before: &str, after: &str) -> (Vec<SemanticToken>, SemanticTokensDelta) {
+        let state = ServerState::default();
+        state.open_doc(URI.to_string(), before.into());
+        let doc = state.docs.get(URI).unwrap();
+        let first = full(&doc, &state.lang);
+        doc.update_text(after.into());
+        let previous = first.result_id.unwrap();
+        match delta(&doc, &state.lang, &previous) {
+            SemanticTokensFullDeltaResult::TokensDelta(d) => (first.data, d),
+            other => panic!("expected a delta, got {other:?}"),
+        }
+    }
+
+    fn apply(old: &[SemanticToken], edits: &[SemanticTokensEdit]) -> Vec<SemanticToken> {
+        let mut out = old.to_vec();
+        for edit in edits {
+            let start = edit.start as usize / 5;
+            let end = start + edit.delete_count as usize / 5;
+            out.splice(start..end, edit.data.clone().unwrap_or_default());
+        }
+        out
+    }
+
+    fn tokens(text: &str) -> Vec<SemanticToken> {
+        let state = ServerState::default();
+        state.open_doc(URI.to_string(), text.into());
+        semantic_tokens(&state.docs.get(URI).unwrap(), &state.lang)
+    }
+
+    #[test]
+    fn delta_of_an_insert() {
+        let before = "f(x) = x\nh(z) = z\n";
+        let after = "f(x) = x\ng(y) = y\nh(z) = z\n";
+        let (old, d) = delta_after(before, after);
+        assert_eq!(d.edits.len(), 1);
+        assert_eq!(d.edits[0].delete_count, 0);
+        assert_eq!(apply(&old, &d.edits), tokens(after));
+    }
+
+    #[test]
+    fn delta_of_a_delete() {
+        let before = "f(x) = x\ng(y) = y\nh(z) = z\n";
+        let after = "f(x) = x\nh(z) = z\n";
+        let (old, d) = delta_after(before, after);
+        assert_eq!(d.edits.len(), 1);
+        assert!(d.edits[0].data.as_ref().unwrap().len() < d.edits[0].delete_count as usize / 5);
+        assert_eq!(apply(&old, &d.edits), tokens(after));
+    }
+
+    #[test]
+    fn delta_of_unchanged_tokens_is_empty() {
+        let text = "f(x) = x\n";
+        let (old, d) = delta_after(text, text);
+        assert!(!old.is_empty());
+        assert!(d.edits.is_empty());
+    }
+
+    #[test]
+    fn stale_previous_id_gets_full_tokens() {
+        let state = ServerState::default();
+        state.open_doc(URI.to_string(), "f(x) = x\n".into());
+        let doc = state.docs.get(URI).unwrap();
+        let first = full(&doc, &state.lang).result_id.unwrap();
+        full(&doc, &state.lang);
+        match delta(&doc, &state.lang, &first) {
+            SemanticTokensFullDeltaResult::Tokens(t) => {
+                assert_eq!(t.data, tokens("f(x) = x\n"));
+                assert_ne!(t.result_id, Some(first));
+            }
+            other => panic!("expected full tokens, got {other:?}"),
+        }
+    }
+}
@@ -1,102 +1,549 @@
-use crate::index::SymbolIndex;
+use crate::cache;
+use crate::config::ParsecConfig;
+use crate::index::{ReferenceIndex, SymbolIndex};
+use crate::line_index::LineIndex;
+use crate::parse;
+use crate::progress::WorkspaceProgressReporter;
 use crate::symbols;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use ignore::WalkBuilder;
-use parking_lot::RwLock;
-use std::collections::HashSet;
+use ignore::overrides::{Override, OverrideBuilder};
+use lru::LruCache;
+use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::task;
-use tree_sitter::{Language, Parser, Tree};
+use tower_lsp::lsp_types::{FileChangeType, Range, SemanticToken};
+use tracing::{error, info, warn};
+use tree_sitter::{InputEdit, Language, Parser, Point, Tree};
 use url::Url;
 
+// Documents whose tree was used most recently come first. The cache itself is
+// unbounded; the eviction loop trims the trees it points at.
+type RecentTrees = Arc<Mutex<LruCache<String, Instant>>>;
+
+// How often the eviction loop checks the number of live trees.
+const EVICT_EVERY: Duration = Duration::from_secs(30);
+
+// Edits take the text lock only, so an update goes through a shared
+// DashMap::get and never blocks readers of other documents in the shard.
 pub struct DocState {
-    text: Arc<str>,
+    text: RwLock<Arc<str>>,
+    parsed_text: RwLock<Arc<str>>,
     tree: RwLock<Option<Tree>>,
     last_edit: RwLock<Instant>,
     last_parse: RwLock<Instant>,
+    version: AtomicI32,
+    pending_edits: Mutex<Vec<InputEdit>>,
+    full_reparse: AtomicBool,
+    uri: String,
+    recent: RecentTrees,
+    // The last semantic tokens sent for this document and their result id,
+    // which a delta request diffs against.
+    last_tokens: Mutex<Option<(String, Vec<SemanticToken>)>>,
+    // Modification time of the file when this text was read from disk; None
+    // for editor buffers. The symbol cache records it for the document.
+    disk_mtime: Option<(u64, u32)>,
 }
 
 impl DocState {
-    pub fn new(text: Arc<str>) -> Self {
+    fn new(uri: String, text: Arc<str>, recent: RecentTrees) -> Self {
         let now = Instant::now();
         Self {
-            text,
+            parsed_text: RwLock::new(text.clone()),
+            text: RwLock::new(text),
             tree: RwLock::new(None),
             last_edit: RwLock::new(now),
             last_parse: RwLock::new(Instant::now() - Duration::from_secs(1)),
+            version: AtomicI32::new(0),
+            pending_edits: Mutex::new(Vec::new()),
+            full_reparse: AtomicBool::new(true),
+            uri,
+            recent,
+            last_tokens: Mutex::new(None),
+            disk_mtime: None,
         }
     }
 
-    pub fn update_text(&mut self, text: Arc<str>) {
-        self.text = text;
+    fn with_disk_mtime(mut self, mtime: Option<(u64, u32)>) -> Self {
+        self.disk_mtime = mtime;
+        self
+    }
+
+    pub fn disk_mtime(&self) -> Option<(u64, u32)> {
+        self.disk_mtime
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    // The edit bookkeeping happens under the text lock so a parse always sees
+    // a text together with exactly the edits that produced it.
+    pub fn update_text(&self, text: Arc<str>) {
+        let mut current = self.text.write();
+        *current = text;
+        self.pending_edits.lock().clear();
+        self.full_reparse.store(true, Ordering::Release);
         *self.last_edit.write() = Instant::now();
     }
 
+    pub fn apply_change(&self, range: Range, new_text: &str) {
+        let mut current = self.text.write();
+        let idx = LineIndex::new(&current);
+        let len = current.len();
+        let start = idx.to_byte_offset(range.start).unwrap_or(len).min(len);
+        let old_end = idx
+            .to_byte_offset(range.end)
+            .unwrap_or(len)
+            .clamp(start, len);
+        let mut text = String::with_capacity(len - (old_end - start) + new_text.len());
+        text.push_str(&current[..start]);
+        text.push_str(new_text);
+        text.push_str(&current[old_end..]);
+
+        let start_position = point_at(&idx, start);
+        let new_end_position = match new_text.rfind('\n') {
+            Some(last) => Point::new(
+                start_position.row + new_text.matches('\n').count(),
+                new_text.len() - last - 1,
+            ),
+            None => Point::new(start_position.row, start_position.column + new_text.len()),
+        };
+        self.record_edit(InputEdit {
+            start_byte: start,
+            old_end_byte: old_end,
+            new_end_byte: start + new_text.len(),
+            start_position,
+            old_end_position: point_at(&idx, old_end),
+            new_end_position,
+        });
+        *current = text.into();
+        *self.last_edit.write() = Instant::now();
+    }
+
+    pub fn record_edit(&self, edit: InputEdit) {
+        self.pending_edits.lock().push(edit);
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    pub fn set_version(&self, v: i32) {
+        self.version.store(v, Ordering::Release);
+    }
+
+    // Records the tokens just sent and returns the ones sent before them.
+    pub fn swap_tokens(
+        &self,
+        result_id: String,
+        tokens: Vec<SemanticToken>,
+    ) -> Option<(String, Vec<SemanticToken>)> {
+        self.last_tokens.lock().replace((result_id, tokens))
+    }
+
     pub fn text(&self) -> String {
-        self.text.to_string()
+        self.text.read().to_string()
     }
 
     pub fn parse_with_debounce(&self, lang: &Language, min_delay: Duration) {
         let edited_at = *self.last_edit.read();
         let parsed_at = *self.last_parse.read();
         if parsed_at >= edited_at && self.tree.read().is_some() {
+            self.touch();
             return;
         }
         if edited_at.elapsed() < min_delay && self.tree.read().is_some() {
+            self.touch();
             return;
         }
-        let mut parser = Parser::new();
-        parser.set_language(lang).unwrap();
-        let tree = parser.parse(&*self.text, None);
-        *self.tree.write() = tree;
-        *self.last_parse.write() = Instant::now();
+        let mut current = self.tree.write();
+        let (text, edits, full_reparse, edited_at) = {
+            let text = self.text.read();
+            let edited_at = *self.last_edit.read();
+            if *self.last_parse.read() >= edited_at && current.is_some() {
+                drop(current);
+                self.touch();
+                return;
+            }
+            (
+                text.clone(),
+                std::mem::take(&mut *self.pending_edits.lock()),
+                self.full_reparse.swap(false, Ordering::AcqRel),
+                edited_at,
+            )
+        };
+        let Some(mut parser) = new_parser(lang) else {
+            *current = None;
+            return;
+        };
+        let old_tree = if full_reparse {
+            None
+        } else {
+            current.take().map(|mut old| {
+                for e in &edits {
+                    old.edit(e);
+                }
+                old
+            })
+        };
+        *current = parser.parse(&*text, old_tree.as_ref());
+        *self.parsed_text.write() = text;
+        // Stamped with the edit it includes, so an edit that lands while
+        // parsing still leaves the tree stale.
+        *self.last_parse.write() = edited_at;
+        let parsed = current.is_some();
+        drop(current);
+        if parsed {
+            self.touch();
+        }
+    }
+
+    // Rebuilds the tree from scratch right away, ignoring the debounce and
+    // any pending incremental edits.
+    pub fn force_reparse(&self, lang: &Language) {
+        let mut current = self.tree.write();
+        let (text, edited_at) = {
+            let text = self.text.read();
+            self.pending_edits.lock().clear();
+            self.full_reparse.store(false, Ordering::Release);
+            (text.clone(), *self.last_edit.read())
+        };
+        let Some(mut parser) = new_parser(lang) else {
+            *current = None;
+            return;
+        };
+        *current = parser.parse(&*text, None);
+        *self.parsed_text.write() = text;
+        *self.last_parse.write() = edited_at;
+        drop(current);
+        self.touch();
+    }
+
+    fn touch(&self) {
+        let mut recent = self.recent.lock();
+        match recent.get_mut(&self.uri) {
+            Some(used) => *used = Instant::now(),
+            None => {
+                recent.put(self.uri.clone(), Instant::now());
+            }
+        }
+    }
+
+    // Frees the tree but keeps the text; the next parse_with_debounce builds
+    // it again from scratch.
+    pub fn drop_tree(&self) {
+        *self.tree.write() = None;
+    }
+
+    // The tree together with the text it was built from; the text lags `text()`
+    // while a parse is being debounced.
+    pub fn parsed_snapshot(&self) -> (Option<Tree>, String) {
+        let tree = self.tree.read();
+        (tree.clone(), self.parsed_text.read().to_string())
     }
+}
 
-    pub fn current_tree(&self) -> Option<Tree> {
-        self.tree.read().clone()
+// A grammar mismatch fails every parse the same way, so it is logged once;
+// the client hears about it from `initialized`.
+fn new_parser(lang: &Language) -> Option<Parser> {
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+    match parse::parser_for(lang) {
+        Ok(parser) => Some(parser),
+        Err(e) => {
+            if !REPORTED.swap(true, Ordering::Relaxed) {
+                error!("parsing disabled: {e:#}");
+            }
+            None
+        }
     }
 }
 
+fn point_at(idx: &LineIndex, byte: usize) -> Point {
+    // Tree-sitter columns count every byte since the last `\n`, including a
+    // trailing `\r`, so this cannot go through the clamped `to_pos`.
+    let line = idx.to_pos(byte).line as usize;
+    Point::new(line, byte - idx.line_start(line))
+}
+
+// Files larger than this are skipped by the indexer; generated data files in
+// packages can run to many megabytes. Zero disables the limit.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
 pub struct ServerState {
     pub docs: Arc<DashMap<String, DocState>>,
+    editor_opened: Arc<DashSet<String>>,
     pub lang: Arc<Language>,
-    pub debounce: Duration,
-    root: RwLock<Option<PathBuf>>,
+    config: RwLock<ParsecConfig>,
+    roots: Arc<RwLock<Vec<Url>>>,
+    active_doc: RwLock<Option<String>>,
+    // When each document was last edited or saved; only the newest of a
+    // burst gets reindexed.
+    pending_reindex: DashMap<String, Instant>,
+    boost_active_doc: AtomicBool,
+    pending_file_events: Mutex<HashMap<Url, FileChangeType>>,
+    file_events_scheduled: AtomicBool,
+    watch_coalesce_ms: AtomicU64,
+    index_generation: Arc<AtomicU64>,
+    file_extensions: RwLock<Arc<[String]>>,
+    max_file_size: AtomicU64,
+    use_cache: AtomicBool,
     pub symbols: Arc<SymbolIndex>,
+    pub references: Arc<ReferenceIndex>,
+    recent: RecentTrees,
 }
 
 impl ServerState {
     pub fn insert_doc(&self, uri: String, text: Arc<str>) {
-        self.docs.insert(uri, DocState::new(text));
+        let doc = DocState::new(uri.clone(), text, self.recent.clone());
+        self.docs.insert(uri, doc);
+    }
+
+    pub fn set_roots(&self, roots: Vec<Url>) {
+        *self.roots.write() = roots;
+        // Any indexer still walking the previous root stops at its next file.
+        self.index_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn roots(&self) -> Vec<Url> {
+        self.roots.read().clone()
+    }
+
+    // Unlike set_roots this leaves indexers of the other roots running.
+    pub fn add_roots(&self, added: Vec<Url>) {
+        let mut roots = self.roots.write();
+        for root in added {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+
+    // Indexer runs started for a removed root stop at their next file.
+    // Documents under it are dropped unless the editor has them open or
+    // another root still covers them. Files a removed root pulled in from
+    // depots or the load path are kept, as other roots may share them.
+    pub fn remove_roots(&self, removed: &[Url]) {
+        self.roots.write().retain(|r| !removed.contains(r));
+        let remaining = self.roots();
+        let mut uris: HashSet<String> = self.docs.iter().map(|e| e.key().clone()).collect();
+        uris.extend(self.symbols.doc_uris());
+        uris.extend(self.references.doc_uris());
+        let mut dropped = 0usize;
+        for uri in uris {
+            let Ok(url) = Url::parse(&uri) else {
+                continue;
+            };
+            if self.editor_opened.contains(&uri)
+                || !removed.iter().any(|r| is_under_root(&url, r))
+                || remaining.iter().any(|r| is_under_root(&url, r))
+            {
+                continue;
+            }
+            self.remove_doc(&uri);
+            dropped += 1;
+        }
+        info!("dropped {dropped} documents of removed workspace folders");
+    }
+
+    pub fn set_active_doc(&self, uri: &str) {
+        *self.active_doc.write() = Some(uri.to_string());
+    }
+
+    pub fn active_doc(&self) -> Option<String> {
+        if self.boost_active_doc.load(Ordering::Relaxed) {
+            self.active_doc.read().clone()
+        } else {
+            None
+        }
+    }
+
+    pub fn set_boost_active_doc(&self, enabled: bool) {
+        self.boost_active_doc.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn mark_dirty(&self, uri: &str) -> Instant {
+        let now = Instant::now();
+        self.pending_reindex.insert(uri.to_string(), now);
+        now
+    }
+
+    pub fn take_pending_reindex(&self, uri: &str, marked_at: Instant) -> bool {
+        self.pending_reindex
+            .remove_if(uri, |_, latest| *latest == marked_at)
+            .is_some()
+    }
+
+    // Accepts "jl", ".jl" or "*.jl"; an empty list keeps the current set.
+    // Extensions come from the client, so one that does not make a valid
+    // `*.{ext}` glob is dropped with a warning; with none left the current
+    // list (`jl` by default) stays.
+    pub fn set_file_extensions(&self, extensions: Vec<String>) {
+        let normalized: Vec<String> = extensions
+            .iter()
+            .map(|e| e.trim().trim_start_matches('*').trim_start_matches('.'))
+            .filter(|e| !e.is_empty())
+            .filter(|e| match file_types(&[e.to_string()]) {
+                Ok(_) => true,
+                Err(err) => {
+                    warn!("ignoring file extension {e:?}: {err}");
+                    false
+                }
+            })
+            .map(str::to_string)
+            .collect();
+        if !normalized.is_empty() {
+            *self.file_extensions.write() = normalized.into();
+        }
+    }
+
+    pub fn file_extensions(&self) -> Arc<[String]> {
+        self.file_extensions.read().clone()
+    }
+
+    pub fn is_indexable(&self, path: &Path) -> bool {
+        is_indexable_path(path, &self.file_extensions())
+    }
+
+    // Returns the configuration it replaced.
+    pub fn update_config(&self, config: ParsecConfig) -> ParsecConfig {
+        std::mem::replace(&mut *self.config.write(), config)
+    }
+
+    pub fn config(&self) -> ParsecConfig {
+        self.config.read().clone()
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.config.read().debounce_ms)
+    }
+
+    pub fn set_max_file_size(&self, bytes: u64) {
+        self.max_file_size.store(bytes, Ordering::Relaxed);
     }
 
-    pub fn set_root(&self, path: PathBuf) {
-        *self.root.write() = Some(path);
+    pub fn set_watch_coalesce(&self, window: Duration) {
+        self.watch_coalesce_ms
+            .store(window.as_millis() as u64, Ordering::Relaxed);
     }
 
-    pub fn root_path(&self) -> Option<PathBuf> {
-        self.root.read().clone()
+    pub fn watch_coalesce(&self) -> Duration {
+        Duration::from_millis(self.watch_coalesce_ms.load(Ordering::Relaxed))
     }
 
-    pub fn start_indexer(&self, root: PathBuf) {
+    // Returns true when the caller is responsible for scheduling the flush.
+    pub fn queue_file_events(&self, events: Vec<(Url, FileChangeType)>) -> bool {
+        let mut pending = self.pending_file_events.lock();
+        for (uri, typ) in events {
+            match (pending.get(&uri).copied(), typ) {
+                (Some(FileChangeType::CREATED), FileChangeType::DELETED) => {
+                    pending.remove(&uri);
+                }
+                (Some(FileChangeType::DELETED), FileChangeType::CREATED) => {
+                    pending.insert(uri, FileChangeType::CHANGED);
+                }
+                (Some(FileChangeType::CREATED), FileChangeType::CHANGED) => {}
+                _ => {
+                    pending.insert(uri, typ);
+                }
+            }
+        }
+        !self.file_events_scheduled.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn flush_file_events(&self) {
+        let batch: Vec<(Url, FileChangeType)> = {
+            let mut pending = self.pending_file_events.lock();
+            self.file_events_scheduled.store(false, Ordering::Release);
+            pending.drain().collect()
+        };
+        info!("applying {} coalesced file events", batch.len());
+        for (uri, typ) in batch {
+            let Ok(path) = uri.to_file_path() else {
+                continue;
+            };
+            // The editor's buffer is authoritative for open documents; the
+            // on-disk copy is picked up again by close_doc.
+            if self.editor_opened.contains(uri.as_str()) {
+                continue;
+            }
+            match typ {
+                FileChangeType::CREATED | FileChangeType::CHANGED => self.reload_from_disk(&path),
+                FileChangeType::DELETED => self.remove_doc(uri.as_str()),
+                _ => {}
+            }
+        }
+    }
+
+    // The returned handle completes once the run has finished, including
+    // writing the cache.
+    pub fn start_indexer(
+        &self,
+        root: PathBuf,
+        progress: Option<Arc<WorkspaceProgressReporter>>,
+    ) -> task::JoinHandle<()> {
         let docs = self.docs.clone();
         let lang = self.lang.clone();
-        let debounce = self.debounce;
+        let debounce = self.debounce();
         let symbols = self.symbols.clone();
+        let references = self.references.clone();
+
+        let cache_file = self
+            .use_cache
+            .load(Ordering::Relaxed)
+            .then(|| cache::cache_path(&root))
+            .flatten();
+
+        let guard = IndexGuard {
+            generation: self.index_generation.clone(),
+            expected: self.index_generation.load(Ordering::Acquire),
+            editor_opened: self.editor_opened.clone(),
+            extensions: self.file_extensions(),
+            max_file_size: self.max_file_size.load(Ordering::Relaxed),
+            exclude: self.config.read().indexing_exclude_patterns.clone().into(),
+            threads: self.config.read().max_indexing_threads,
+            recent: self.recent.clone(),
+            root: root.clone(),
+            roots: self.roots.clone(),
+            visited: Arc::new(DashSet::new()),
+            cache_file: cache_file.clone(),
+            cached: Arc::new(OnceLock::new()),
+            progress,
+        };
 
-        let mut roots = vec![root.clone()];
-        roots.extend(discover_env_roots(&root));
+        let roots = unique_roots(std::iter::once(root.clone()).chain(discover_env_roots(&root)));
         let mut handles = Vec::new();
 
         for r in roots {
             let docs_cloned = docs.clone();
             let lang = lang.clone();
             let symbols = symbols.clone();
+            let references = references.clone();
+            let guard = guard.clone();
             let handle = task::spawn_blocking(move || {
-                index_workspace(&r, docs_cloned, &lang, debounce, &symbols);
+                // Whichever root gets here first reads the cache; the others
+                // wait for it, so no root parses a file the cache covers.
+                guard.cached.get_or_init(|| {
+                    match guard.cache_file.as_deref().filter(|p| p.is_file()) {
+                        Some(path) => load_cache(path, &symbols, &references, &guard.editor_opened),
+                        None => DashSet::new(),
+                    }
+                });
+                index_workspace(
+                    &r,
+                    docs_cloned,
+                    &lang,
+                    debounce,
+                    &symbols,
+                    &references,
+                    &guard,
+                );
             });
             handles.push(handle);
         }
@@ -104,21 +551,204 @@ impl ServerState {
             for h in handles {
                 let _ = h.await;
             }
-        });
+            if let Some(progress) = &guard.progress {
+                progress.finish().await;
+            }
+            let Some(path) = cache_file else {
+                return;
+            };
+            if guard.cancelled() {
+                return;
+            }
+            let _ = task::spawn_blocking(move || {
+                // The index is shared by every root; this root's cache only
+                // gets the files its own run walked.
+                let own: HashSet<String> = guard
+                    .visited
+                    .iter()
+                    .filter_map(|p| path_to_file_uri(&p))
+                    .collect();
+                let mtime = |uri: &str| {
+                    if !own.contains(uri) || guard.editor_opened.contains(uri) {
+                        return None;
+                    }
+                    docs.get(uri)?.disk_mtime()
+                };
+                if let Err(e) = cache::save(&symbols, &references, &path, mtime) {
+                    warn!("failed to write symbol cache {}: {e:#}", path.display());
+                }
+            })
+            .await;
+        })
     }
 
-    pub fn reindex_doc(&self, uri_str: &str) {
-        if let Ok(url) = Url::parse(uri_str) {
-            if let Some(entry) = self.docs.get(uri_str) {
-                let syms = symbols::extract_workspace_symbols_with_cache(
-                    &entry,
-                    &self.lang,
-                    self.debounce,
-                    &url,
-                );
-                self.symbols.upsert_doc(&url, syms);
+    // Drops everything the indexer built so start_indexer can rebuild it from
+    // disk. Documents open in the editor are indexed again straight away
+    // from their buffers.
+    pub fn reset_index(&self) {
+        self.index_generation.fetch_add(1, Ordering::AcqRel);
+        self.symbols.clear();
+        self.references.clear();
+        self.docs.retain(|uri, _| self.editor_opened.contains(uri));
+        let open: Vec<String> = self.editor_opened.iter().map(|u| u.clone()).collect();
+        for uri in open {
+            self.reindex_doc(&uri);
+        }
+    }
+
+    // Every workspace file is parsed once while indexing, so without this
+    // the server would hold a tree for each of them indefinitely. Only the
+    // trees go; text, symbols and references stay.
+    pub fn start_eviction_loop(self: &Arc<Self>) -> task::JoinHandle<()> {
+        let state = self.clone();
+        task::spawn(async move {
+            let mut tick = tokio::time::interval(EVICT_EVERY);
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                state.evict_trees();
+            }
+        })
+    }
+
+    fn evict_trees(&self) {
+        let limit = self.config.read().max_parse_trees;
+        if limit == 0 {
+            return;
+        }
+        let mut evicted = 0usize;
+        loop {
+            // Popped under the lock and dropped after it, since a parse holds
+            // the tree lock while it touches the cache.
+            let uri = {
+                let mut recent = self.recent.lock();
+                if recent.len() <= limit {
+                    break;
+                }
+                match recent.pop_lru() {
+                    Some((uri, _)) => uri,
+                    None => break,
+                }
+            };
+            if let Some(doc) = self.docs.get(&uri) {
+                doc.drop_tree();
+                evicted += 1;
             }
         }
+        if evicted > 0 {
+            info!("dropped {evicted} least recently used parse trees");
+        }
+    }
+
+    pub fn set_use_cache(&self, enabled: bool) {
+        self.use_cache.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn inlay_hints(&self) -> bool {
+        self.config.read().inlay_hints_enabled
+    }
+
+    pub fn duplicate_method_lint(&self) -> bool {
+        self.config.read().duplicate_methods_enabled
+    }
+
+    pub fn reload_from_disk(&self, path: &Path) {
+        if !within_size_limit(path, self.max_file_size.load(Ordering::Relaxed)) {
+            if let Some(uri) = path_to_file_uri(path) {
+                self.remove_doc(&uri);
+            }
+            return;
+        }
+        // Taken before the read, so a write in between leaves the recorded
+        // time older than the text and the cache entry is discarded.
+        let mtime = cache::mtime_of(path);
+        if let Ok(text) = fs::read_to_string(path)
+            && let Some(uri) = path_to_file_uri(path)
+        {
+            let doc = DocState::new(uri.clone(), text.into(), self.recent.clone());
+            self.docs.insert(uri.clone(), doc.with_disk_mtime(mtime));
+            self.reindex_doc(&uri);
+        }
+    }
+
+    pub fn open_doc(&self, uri: String, text: Arc<str>) {
+        self.editor_opened.insert(uri.clone());
+        self.insert_doc(uri, text);
+    }
+
+    pub fn is_open(&self, uri: &str) -> bool {
+        self.editor_opened.contains(uri)
+    }
+
+    // The user's own documents: those under a workspace folder and those
+    // open in the editor, but not the depot and load-path packages the
+    // indexer pulled in.
+    pub fn workspace_doc_uris(&self) -> Vec<String> {
+        let roots = self.roots();
+        self.docs
+            .iter()
+            .map(|e| e.key().clone())
+            .filter(|uri| {
+                self.editor_opened.contains(uri)
+                    || Url::parse(uri).is_ok_and(|url| roots.iter().any(|r| is_under_root(&url, r)))
+            })
+            .collect()
+    }
+
+    pub fn close_doc(&self, uri_str: &str) {
+        if self.editor_opened.remove(uri_str).is_none() {
+            return;
+        }
+        let on_disk = Url::parse(uri_str)
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+            .filter(|p| p.is_file() && self.is_indexable(p) && self.is_indexed_location(p));
+        match on_disk {
+            Some(path) => self.reload_from_disk(&path),
+            None => self.remove_doc(uri_str),
+        }
+    }
+
+    // Whether the indexer would pick up `path`: it sits under a workspace
+    // folder or under one of the depot and load-path directories discovered
+    // for one. Anything else was only indexed while the editor had it open.
+    fn is_indexed_location(&self, path: &Path) -> bool {
+        self.roots()
+            .iter()
+            .filter_map(|r| r.to_file_path().ok())
+            .any(|root| {
+                path.starts_with(&root)
+                    || discover_env_roots(&root)
+                        .iter()
+                        .any(|env| path.starts_with(env))
+            })
+    }
+
+    pub fn remove_doc(&self, uri_str: &str) {
+        self.editor_opened.remove(uri_str);
+        self.docs.remove(uri_str);
+        self.recent.lock().pop(uri_str);
+        if let Ok(url) = Url::parse(uri_str) {
+            self.symbols.remove_doc(&url);
+            self.references.remove_doc(&url);
+        }
+    }
+
+    pub fn reindex_doc(&self, uri_str: &str) {
+        if let Ok(url) = Url::parse(uri_str)
+            && let Some(entry) = self.docs.get(uri_str)
+        {
+            let syms = symbols::extract_workspace_symbols_with_cache(
+                &entry,
+                &self.lang,
+                self.debounce(),
+                &url,
+            );
+            self.symbols.upsert_doc(&url, syms);
+            let occurrences =
+                symbols::extract_identifier_occurrences(&entry, &self.lang, self.debounce());
+            self.references.upsert_doc(&url, occurrences);
+        }
     }
 }
 
@@ -126,26 +756,131 @@ impl Default for ServerState {
     fn default() -> Self {
         Self {
             docs: Arc::new(DashMap::new()),
+            editor_opened: Arc::new(DashSet::new()),
             lang: Arc::new(tree_sitter_julia::LANGUAGE.into()),
-            debounce: Duration::from_millis(120),
-            root: RwLock::new(None),
+            config: RwLock::new(ParsecConfig::default()),
+            roots: Arc::new(RwLock::new(Vec::new())),
+            active_doc: RwLock::new(None),
+            pending_reindex: DashMap::new(),
+            boost_active_doc: AtomicBool::new(true),
+            pending_file_events: Mutex::new(HashMap::new()),
+            file_events_scheduled: AtomicBool::new(false),
+            watch_coalesce_ms: AtomicU64::new(200),
+            index_generation: Arc::new(AtomicU64::new(0)),
+            file_extensions: RwLock::new(Arc::from(vec!["jl".to_string()])),
+            max_file_size: AtomicU64::new(DEFAULT_MAX_FILE_SIZE),
+            use_cache: AtomicBool::new(true),
             symbols: Arc::new(SymbolIndex::default()),
+            references: Arc::new(ReferenceIndex::default()),
+            recent: Arc::new(Mutex::new(LruCache::unbounded())),
+        }
+    }
+}
+
+// Ties an indexer run to the root and settings it was started with.
+// Documents open in the editor are never overwritten with their on-disk
+// contents; reindex_doc owns those.
+#[derive(Clone)]
+struct IndexGuard {
+    generation: Arc<AtomicU64>,
+    expected: u64,
+    editor_opened: Arc<DashSet<String>>,
+    extensions: Arc<[String]>,
+    max_file_size: u64,
+    exclude: Arc<[String]>,
+    threads: usize,
+    recent: RecentTrees,
+    // The workspace folder the run was started for; removing it from roots
+    // cancels the run.
+    root: PathBuf,
+    roots: Arc<RwLock<Vec<Url>>>,
+    // Files already claimed by one of the run's roots, for roots that nest.
+    visited: Arc<DashSet<PathBuf>>,
+    cache_file: Option<PathBuf>,
+    // Documents served from the cache, which need reading but not parsing.
+    // Filled by the first of the run's roots to start.
+    cached: Arc<OnceLock<DashSet<String>>>,
+    progress: Option<Arc<WorkspaceProgressReporter>>,
+}
+
+impl IndexGuard {
+    fn cancelled(&self) -> bool {
+        self.generation.load(Ordering::Acquire) != self.expected
+            || !self
+                .roots
+                .read()
+                .iter()
+                .any(|r| r.to_file_path().is_ok_and(|p| p == self.root))
+    }
+
+    fn may_write(&self, uri: &str) -> bool {
+        !self.cancelled() && !self.editor_opened.contains(uri)
+    }
+}
+
+// Serves symbols and references from the previous session. Returns the
+// documents it loaded: the indexer still reads those into docs but skips
+// parsing them. Files changed since the cache was written are left to the
+// indexer.
+fn load_cache(
+    path: &Path,
+    symbols: &SymbolIndex,
+    references: &ReferenceIndex,
+    editor_opened: &DashSet<String>,
+) -> DashSet<String> {
+    let loaded = DashSet::new();
+    match cache::load(path) {
+        Ok((cached_symbols, cached_references)) => {
+            for (uri, entries) in cached_symbols.snapshot() {
+                if editor_opened.contains(&uri) {
+                    continue;
+                }
+                if let Ok(url) = Url::parse(&uri) {
+                    symbols.insert_entries(&url, entries.to_vec());
+                    references.upsert_doc(&url, cached_references.occurrences(&uri));
+                    loaded.insert(uri);
+                }
+            }
+            info!("loaded {} documents from {}", loaded.len(), path.display());
         }
+        Err(e) => warn!("ignoring symbol cache {}: {e:#}", path.display()),
     }
+    loaded
 }
 
+// Deeper than any real package layout; bounds the walk of a root that
+// points somewhere unexpected, like a home directory.
+const MAX_WALK_DEPTH: usize = 32;
+
 fn index_workspace(
     root: &Path,
     docs: Arc<DashMap<String, DocState>>,
     lang: &Language,
     debounce: Duration,
     symbols: &SymbolIndex,
+    references: &ReferenceIndex,
+    guard: &IndexGuard,
 ) {
-    let mut types = ignore::types::TypesBuilder::new();
-    types.add_defaults();
-    types.select("jl");
-    types.add("jl", "*.jl").unwrap();
-    let types = types.build().unwrap();
+    let types = match file_types(&guard.extensions) {
+        Ok(types) => types,
+        Err(e) => {
+            error!("not indexing {}: {e}", root.display());
+            return;
+        }
+    };
+
+    // Overrides whitelist by default; a leading `!` turns each pattern into
+    // an exclusion instead.
+    let mut overrides = OverrideBuilder::new(root);
+    for pattern in guard.exclude.iter() {
+        if let Err(e) = overrides.add(&format!("!{pattern}")) {
+            warn!("ignoring exclude pattern {pattern:?}: {e}");
+        }
+    }
+    let overrides = overrides.build().unwrap_or_else(|e| {
+        warn!("ignoring exclude patterns: {e}");
+        Override::empty()
+    });
 
     let walker = WalkBuilder::new(root)
         .follow_links(false)
@@ -155,47 +890,173 @@ fn index_workspace(
         .git_global(true)
         .git_exclude(true)
         .types(types)
+        .overrides(overrides)
+        .max_depth(Some(MAX_WALK_DEPTH))
         .build();
 
+    let started = Instant::now();
+    let mut paths = Vec::new();
     for entry in walker.flatten() {
+        if guard.cancelled() {
+            info!("indexing of {} cancelled", root.display());
+            return;
+        }
         let path = entry.path();
-        if let Some(ext) = path.extension() {
-            if ext == "jl" {
-                let is_depot = path.components().any(|c| {
-                    if let std::path::Component::Normal(s) = c {
-                        s == "packages" || s == "dev"
-                    } else {
-                        false
-                    }
-                });
-                if is_depot {
-                    let has_src = path.components().any(|c| {
-                        if let std::path::Component::Normal(s) = c {
-                            s == "src"
-                        } else {
-                            false
-                        }
-                    });
-                    if !has_src {
-                        continue;
-                    }
-                }
-                if let Ok(text) = fs::read_to_string(path) {
-                    if let Some(uri) = path_to_file_uri(path) {
-                        docs.insert(uri.clone(), DocState::new(text.into()));
-                        if let Ok(url) = Url::parse(&uri) {
-                            if let Some(doc) = docs.get(&uri) {
-                                let syms = crate::symbols::extract_workspace_symbols_with_cache(
-                                    &doc, lang, debounce, &url,
-                                );
-                                symbols.upsert_doc(&url, syms);
-                            }
-                        }
-                    }
-                }
+        if is_indexable_path(path, &guard.extensions)
+            && within_size_limit(path, guard.max_file_size)
+            && guard.visited.insert(path.to_path_buf())
+        {
+            paths.push(entry.into_path());
+        }
+    }
+
+    if let Some(progress) = &guard.progress {
+        progress.add_total(paths.len());
+    }
+
+    // Reading, parsing and extraction are independent per file, so they fan
+    // out over a rayon pool; DashMap and the indexes take concurrent writes,
+    // and every parse builds its own Parser. bench_index_workspace indexes
+    // 3000 files (26 MB) in a release build in 17.2s with maxThreads=1 and
+    // 17.6s with one thread per core, measured on a single-core machine, so
+    // there was nothing to fan out over; the speedup with more cores has not
+    // been measured.
+    let index_all = || {
+        paths.par_iter().for_each(|path| {
+            if guard.cancelled() {
+                return;
+            }
+            index_file(path, &docs, lang, debounce, symbols, references, guard);
+            if let Some(progress) = &guard.progress {
+                progress.file_done();
+            }
+        })
+    };
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(guard.threads)
+        .thread_name(|i| format!("parsec-index-{i}"))
+        .build()
+    {
+        Ok(pool) => pool.install(index_all),
+        Err(e) => {
+            warn!("falling back to the global indexing pool: {e}");
+            index_all();
+        }
+    }
+    info!(
+        "indexed {} files under {} in {:?}",
+        paths.len(),
+        root.display(),
+        started.elapsed()
+    );
+}
+
+fn file_types(extensions: &[String]) -> Result<ignore::types::Types, ignore::Error> {
+    let mut types = ignore::types::TypesBuilder::new();
+    for ext in extensions {
+        types.add("parsec", &format!("*.{ext}"))?;
+    }
+    types.select("parsec");
+    types.build()
+}
+
+fn index_file(
+    path: &Path,
+    docs: &DashMap<String, DocState>,
+    lang: &Language,
+    debounce: Duration,
+    symbols: &SymbolIndex,
+    references: &ReferenceIndex,
+    guard: &IndexGuard,
+) {
+    // Taken before the read, as in reload_from_disk.
+    let mtime = cache::mtime_of(path);
+    let Ok(text) = fs::read_to_string(path) else {
+        return;
+    };
+    let Some(uri) = path_to_file_uri(path) else {
+        return;
+    };
+    // Holding the entry keeps open_doc from slipping in between the check
+    // and the insert.
+    let slot = docs.entry(uri.clone());
+    if !guard.may_write(&uri) {
+        return;
+    }
+    slot.insert(
+        DocState::new(uri.clone(), text.into(), guard.recent.clone()).with_disk_mtime(mtime),
+    );
+    // The tree is built on first use, like an evicted one.
+    if guard.cached.get().is_some_and(|c| c.remove(&uri).is_some()) {
+        return;
+    }
+    if let Ok(url) = Url::parse(&uri)
+        && let Some(doc) = docs.get(&uri)
+    {
+        let syms = crate::symbols::extract_workspace_symbols_with_cache(&doc, lang, debounce, &url);
+        let occurrences = crate::symbols::extract_identifier_occurrences(&doc, lang, debounce);
+        drop(doc);
+        if guard.may_write(&uri) {
+            symbols.upsert_doc(&url, syms);
+            references.upsert_doc(&url, occurrences);
+        }
+    }
+}
+
+fn within_size_limit(path: &Path, limit: u64) -> bool {
+    if limit == 0 {
+        return true;
+    }
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() > limit => {
+            warn!(
+                "skipping {} ({} bytes exceeds the {} byte limit)",
+                path.display(),
+                meta.len(),
+                limit
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+fn is_indexable_path(path: &Path, extensions: &[String]) -> bool {
+    if path
+        .extension()
+        .is_none_or(|ext| !extensions.iter().any(|e| ext == e.as_str()))
+    {
+        return false;
+    }
+    let is_depot = path.components().any(|c| {
+        if let std::path::Component::Normal(s) = c {
+            s == "packages" || s == "dev"
+        } else {
+            false
+        }
+    });
+    if is_depot {
+        let has_src = path.components().any(|c| {
+            if let std::path::Component::Normal(s) = c {
+                s == "src"
+            } else {
+                false
             }
+        });
+        if !has_src {
+            return false;
         }
     }
+    true
+}
+
+fn is_under_root(uri: &Url, root: &Url) -> bool {
+    match (uri.to_file_path(), root.to_file_path()) {
+        (Ok(path), Ok(root)) => path.starts_with(root),
+        _ => uri
+            .as_str()
+            .starts_with(root.as_str().trim_end_matches('/')),
+    }
 }
 
 fn path_to_file_uri(path: &Path) -> Option<String> {
@@ -208,13 +1069,52 @@ fn path_to_file_uri(path: &Path) -> Option<String> {
     Some(url.to_string())
 }
 
+// The same directory can come from several depots and the load path,
+// possibly through a symlink. Roots are compared canonically but kept as
+// given, so file URIs match what the editor opens. Roots that do not exist
+// are dropped.
+fn unique_roots(roots: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    roots
+        .into_iter()
+        .filter(|r| fs::canonicalize(r).is_ok_and(|c| seen.insert(c)))
+        .collect()
+}
+
 fn discover_env_roots(root: &Path) -> Vec<PathBuf> {
-    let mut out = Vec::new();
+    let mut out = load_path_roots(root);
     let project_toml = root.join("Project.toml");
     if !project_toml.exists() {
         return out;
     }
-    let depots: Vec<PathBuf> = std::env::var("JULIA_DEPOT_PATH")
+    let deps = read_project_deps(&project_toml);
+    if deps.is_empty() {
+        return out;
+    }
+    // The manifest pins each dependency to one installed version; without
+    // one, every installed version and any dev checkout is indexed.
+    let sources = ["JuliaManifest.toml", "Manifest.toml"]
+        .iter()
+        .map(|f| root.join(f))
+        .find(|p| p.is_file())
+        .map(|m| read_manifest_sources(&m))
+        .unwrap_or_default();
+    let depots = julia_depots();
+    for name in &deps {
+        if let Some(src) = sources.get(name) {
+            out.push(src.clone());
+            continue;
+        }
+        for d in &depots {
+            out.push(d.join("packages").join(name));
+            out.push(d.join("dev").join(name));
+        }
+    }
+    out
+}
+
+fn julia_depots() -> Vec<PathBuf> {
+    std::env::var("JULIA_DEPOT_PATH")
         .ok()
         .map(|s| {
             s.split(':')
@@ -222,32 +1122,493 @@ fn discover_env_roots(root: &Path) -> Vec<PathBuf> {
                 .map(PathBuf::from)
                 .collect()
         })
-        .unwrap_or_else(|| vec![dirs::home_dir().unwrap_or_default().join(".julia")]);
-    let deps = read_project_deps(&project_toml);
-    if deps.is_empty() {
+        .unwrap_or_else(|| vec![dirs::home_dir().unwrap_or_default().join(".julia")])
+}
+
+// Source directory of each manifest entry: its `path` for developed
+// packages, otherwise `packages/<name>/<slug>` in the first depot that has
+// it. Standard libraries have neither and are left out.
+fn read_manifest_sources(manifest: &Path) -> HashMap<String, PathBuf> {
+    let mut out = HashMap::new();
+    let Ok(src) = fs::read_to_string(manifest) else {
         return out;
-    }
-    for d in depots {
-        let pkgs = d.join("packages");
-        let dev = d.join("dev");
-        for name in &deps {
-            out.push(pkgs.join(name));
-            out.push(dev.join(name));
+    };
+    let Ok(doc) = toml::from_str::<toml::Table>(&src) else {
+        warn!("could not parse {}", manifest.display());
+        return out;
+    };
+    // Format 2.0 nests the entries under `deps`; the original format keeps
+    // them at the top level.
+    let entries = match doc.get("deps").and_then(|v| v.as_table()) {
+        Some(deps) if doc.contains_key("manifest_format") => deps,
+        _ => &doc,
+    };
+    let base = manifest.parent().unwrap_or(Path::new("."));
+    let depots = julia_depots();
+    for (name, versions) in entries {
+        let Some(entry) = versions
+            .as_array()
+            .and_then(|a| a.first())
+            .and_then(|e| e.as_table())
+        else {
+            continue;
+        };
+        if let Some(path) = entry.get("path").and_then(|v| v.as_str()) {
+            out.insert(name.clone(), base.join(path));
+            continue;
+        }
+        let (Some(uuid), Some(tree)) = (
+            entry.get("uuid").and_then(|v| v.as_str()),
+            entry.get("git-tree-sha1").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let Some(slug) = version_slug(uuid, tree) else {
+            continue;
+        };
+        if let Some(dir) = depots
+            .iter()
+            .map(|d| d.join("packages").join(name).join(&slug))
+            .find(|p| p.is_dir())
+        {
+            out.insert(name.clone(), dir);
         }
     }
     out
 }
 
+// Base.version_slug: five base-62 digits of the CRC-32C of the UUID (as a
+// little-endian UInt128) followed by the git tree hash.
+fn version_slug(uuid: &str, tree_sha1: &str) -> Option<String> {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let mut uuid = hex_bytes(&uuid.replace('-', ""))?;
+    let tree = hex_bytes(tree_sha1)?;
+    if uuid.len() != 16 || tree.len() != 20 {
+        return None;
+    }
+    uuid.reverse();
+    let mut crc = crc32c(crc32c(0, &uuid), &tree);
+    let mut slug = String::with_capacity(5);
+    for _ in 0..5 {
+        slug.push(CHARS[(crc % 62) as usize] as char);
+        crc /= 62;
+    }
+    Some(slug)
+}
+
+fn hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub(crate) fn crc32c(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in bytes {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// JULIA_LOAD_PATH entries are used as they are. Of the `@` forms only the
+// bare `@` (the active project, here the workspace root) is a directory we
+// know; `@stdlib`, `@v#.#` and other named environments are skipped, as are
+// the empty entries Julia expands to its default load path.
+fn load_path_roots(root: &Path) -> Vec<PathBuf> {
+    match std::env::var_os("JULIA_LOAD_PATH") {
+        Some(value) => parse_load_path(&value, root),
+        None => Vec::new(),
+    }
+}
+
+fn parse_load_path(value: &std::ffi::OsStr, root: &Path) -> Vec<PathBuf> {
+    std::env::split_paths(value)
+        .filter_map(|p| {
+            let entry = p.to_string_lossy();
+            if entry.is_empty() {
+                None
+            } else if entry == "@" {
+                Some(root.to_path_buf())
+            } else if entry.starts_with('@') {
+                None
+            } else {
+                Some(PathBuf::from(shellexpand::tilde(&entry).as_ref()))
+            }
+        })
+        .collect()
+}
+
 fn read_project_deps(file: &Path) -> HashSet<String> {
     let mut out = HashSet::new();
-    if let Ok(src) = std::fs::read_to_string(file) {
-        if let Ok(doc) = toml::from_str::<toml::Value>(&src) {
-            if let Some(deps) = doc.get("deps").and_then(|v| v.as_table()) {
-                for (name, _) in deps {
-                    out.insert(name.clone());
-                }
-            }
+    if let Ok(src) = std::fs::read_to_string(file)
+        && let Ok(doc) = toml::from_str::<toml::Value>(&src)
+        && let Some(deps) = doc.get("deps").and_then(|v| v.as_table())
+    {
+        for (name, _) in deps {
+            out.insert(name.clone());
         }
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    fn doc(text: &str) -> DocState {
+        let recent = Arc::new(Mutex::new(LruCache::unbounded()));
+        DocState::new("file:///t.jl".into(), text.into(), recent)
+    }
+
+    // Columns are UTF-16, the encoding a server uses until a client offers
+    // another.
+    #[test]
+    fn apply_change_after_multibyte_character() {
+        let lang = parse::julia_lang();
+        let doc = doc("∇f(x) = 1\ng(y) = 2\n");
+        doc.parse_with_debounce(&lang, Duration::ZERO);
+
+        // `f` is the second UTF-16 unit of the line but starts at byte 3.
+        doc.apply_change(Range::new(Position::new(0, 1), Position::new(0, 2)), "hh");
+        assert_eq!(doc.text(), "∇hh(x) = 1\ng(y) = 2\n");
+        {
+            let edits = doc.pending_edits.lock();
+            assert_eq!(edits.len(), 1);
+            assert_eq!(
+                (
+                    edits[0].start_byte,
+                    edits[0].old_end_byte,
+                    edits[0].new_end_byte
+                ),
+                (3, 4, 5)
+            );
+            assert_eq!(edits[0].start_position, Point::new(0, 3));
+            assert_eq!(edits[0].new_end_position, Point::new(0, 5));
+        }
+
+        doc.apply_change(Range::new(Position::new(1, 0), Position::new(1, 1)), "σ");
+        assert_eq!(doc.text(), "∇hh(x) = 1\nσ(y) = 2\n");
+
+        doc.parse_with_debounce(&lang, Duration::ZERO);
+        let (tree, text) = doc.parsed_snapshot();
+        let tree = tree.unwrap();
+        let full = parse::parse(&text, None).unwrap();
+        assert_eq!(tree.root_node().to_sexp(), full.root_node().to_sexp());
+        let name = tree.root_node().descendant_for_byte_range(3, 3).unwrap();
+        assert_eq!(name.byte_range(), 0..5);
+    }
+
+    #[test]
+    fn invalid_extensions_are_dropped() {
+        let state = ServerState::default();
+        state.set_file_extensions(vec!["[".into(), "*.{".into()]);
+        assert_eq!(&*state.file_extensions(), &["jl".to_string()]);
+        state.set_file_extensions(vec![".jl".into(), "[".into(), "*.jmd".into()]);
+        assert_eq!(
+            &*state.file_extensions(),
+            &["jl".to_string(), "jmd".to_string()]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn configured_extensions_are_indexed() {
+        let root = std::env::temp_dir().join(format!("parsec-ext-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.jl"), "alpha() = 1\n").unwrap();
+        fs::write(root.join("b.jmd"), "beta() = 1\n").unwrap();
+        fs::write(root.join("c.txt"), "gamma() = 1\n").unwrap();
+
+        let state = ServerState::default();
+        state.set_use_cache(false);
+        state.set_file_extensions(vec![".jl".into(), ".jmd".into()]);
+        state.set_roots(vec![Url::from_file_path(&root).unwrap()]);
+        state.start_indexer(root.clone(), None).await.unwrap();
+
+        let mut names: Vec<String> = state
+            .symbols
+            .snapshot()
+            .iter()
+            .flat_map(|(_, entries)| entries.iter().map(|e| e.name.to_string()))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["alpha", "beta"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn workspace_docs_exclude_dependencies() {
+        let state = ServerState::default();
+        state.set_roots(vec![Url::parse("file:///ws/").unwrap()]);
+        state.insert_doc("file:///ws/src/a.jl".into(), "".into());
+        state.insert_doc("file:///depot/packages/Dep/src/Dep.jl".into(), "".into());
+        state.open_doc("file:///elsewhere/scratch.jl".into(), "".into());
+        let mut uris = state.workspace_doc_uris();
+        uris.sort();
+        assert_eq!(
+            uris,
+            ["file:///elsewhere/scratch.jl", "file:///ws/src/a.jl"]
+        );
+    }
+
+    #[test]
+    fn closing_a_file_outside_the_roots_drops_it() {
+        let base = std::env::temp_dir().join(format!("parsec-close-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (root, outside) = (base.join("ws"), base.join("stray"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("a.jl"), "alpha() = 1\n").unwrap();
+        fs::write(outside.join("b.jl"), "beta() = 1\n").unwrap();
+
+        let state = ServerState::default();
+        state.set_roots(vec![Url::from_directory_path(&root).unwrap()]);
+        let inside = path_to_file_uri(&root.join("a.jl")).unwrap();
+        let stray = path_to_file_uri(&outside.join("b.jl")).unwrap();
+        for uri in [&inside, &stray] {
+            state.open_doc(uri.clone(), "edited() = 1\n".into());
+            state.reindex_doc(uri);
+            state.close_doc(uri);
+        }
+
+        assert_eq!(state.docs.get(&inside).unwrap().text(), "alpha() = 1\n");
+        assert!(state.docs.get(&stray).is_none());
+        assert_eq!(state.symbols.doc_uris(), [inside]);
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn apply_change_inserting_lines() {
+        let doc = doc("α = 1\n");
+        doc.apply_change(
+            Range::new(Position::new(0, 5), Position::new(0, 5)),
+            "\nβ = 2",
+        );
+        assert_eq!(doc.text(), "α = 1\nβ = 2\n");
+        let edits = doc.pending_edits.lock();
+        assert_eq!(edits[0].start_byte, 6);
+        assert_eq!(edits[0].new_end_position, Point::new(1, "β = 2".len()));
+    }
+
+    #[test]
+    fn crc32c_known_answer() {
+        assert_eq!(crc32c(0, b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(crc32c(0, b"1234"), b"56789"), 0xE306_9283);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_path_roots_skip_named_environments_and_repeat_once() {
+        let root = std::env::temp_dir().join(format!("parsec-load-path-{}", std::process::id()));
+        let pkgs = root.join("pkgs");
+        fs::create_dir_all(&pkgs).unwrap();
+        let link = root.join("pkgs-link");
+        let _ = std::os::unix::fs::symlink(&pkgs, &link);
+
+        let value = format!(
+            "{}::@:@stdlib:@v1.10:{}:{}/missing",
+            pkgs.display(),
+            link.display(),
+            root.display()
+        );
+        let roots = parse_load_path(std::ffi::OsStr::new(&value), &root);
+        assert_eq!(
+            roots,
+            [pkgs.clone(), root.clone(), link, root.join("missing")]
+        );
+        let unique = unique_roots(std::iter::once(root.clone()).chain(roots));
+        assert_eq!(unique, [root.clone(), pkgs]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn active_document_boost_can_be_disabled() {
+        let state = ServerState::default();
+        state.set_active_doc("file:///ws/a.jl");
+        assert_eq!(state.active_doc().as_deref(), Some("file:///ws/a.jl"));
+        state.set_boost_active_doc(false);
+        assert_eq!(state.active_doc(), None);
+    }
+
+    #[test]
+    fn saving_a_renamed_function_reindexes_once() {
+        let state = ServerState::default();
+        let uri = "file:///ws/a.jl";
+        state.open_doc(uri.into(), "function old_name(x)\n    x\nend\n".into());
+        state.reindex_doc(uri);
+        assert_eq!(state.symbols.search_exact("old_name", &[]).len(), 1);
+
+        let doc = state.docs.get(uri).unwrap();
+        doc.apply_change(
+            Range::new(Position::new(0, 9), Position::new(0, 17)),
+            "new_name",
+        );
+        // did_save reparses at once rather than waiting out the debounce.
+        doc.force_reparse(&state.lang);
+        drop(doc);
+        // A burst of saves leaves only the last one to reindex.
+        let first = state.mark_dirty(uri);
+        std::thread::sleep(Duration::from_millis(1));
+        let last = state.mark_dirty(uri);
+        assert!(!state.take_pending_reindex(uri, first));
+        assert!(state.take_pending_reindex(uri, last));
+        state.reindex_doc(uri);
+
+        assert!(state.symbols.search_exact("old_name", &[]).is_empty());
+        assert_eq!(state.symbols.search_exact("new_name", &[]).len(), 1);
+    }
+
+    #[test]
+    fn deleted_file_event_drops_its_symbols() {
+        let root = std::env::temp_dir().join(format!("parsec-watch-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("shapes.jl");
+        fs::write(&path, "struct Circle end\n").unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+
+        let state = ServerState::default();
+        state.queue_file_events(vec![(uri.clone(), FileChangeType::CREATED)]);
+        state.flush_file_events();
+        assert_eq!(state.symbols.search_exact("Circle", &[]).len(), 1);
+
+        fs::remove_file(&path).unwrap();
+        state.queue_file_events(vec![(uri.clone(), FileChangeType::DELETED)]);
+        state.flush_file_events();
+        assert!(state.symbols.search_exact("Circle", &[]).is_empty());
+        assert!(state.docs.get(uri.as_str()).is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn watched_file_bursts_coalesce() {
+        let state = ServerState::default();
+        let uri = |name: &str| Url::parse(&format!("file:///ws/{name}")).unwrap();
+        let mut scheduled = 0;
+        for _ in 0..50 {
+            if state.queue_file_events(vec![(uri("a.jl"), FileChangeType::CHANGED)]) {
+                scheduled += 1;
+            }
+        }
+        let burst = vec![
+            (uri("tmp.jl"), FileChangeType::CREATED),
+            (uri("tmp.jl"), FileChangeType::DELETED),
+            (uri("b.jl"), FileChangeType::DELETED),
+            (uri("b.jl"), FileChangeType::CREATED),
+            (uri("c.jl"), FileChangeType::CREATED),
+            (uri("c.jl"), FileChangeType::CHANGED),
+        ];
+        if state.queue_file_events(burst) {
+            scheduled += 1;
+        }
+        assert_eq!(scheduled, 1);
+        {
+            let pending = state.pending_file_events.lock();
+            let mut events: Vec<(&str, FileChangeType)> =
+                pending.iter().map(|(u, t)| (u.path(), *t)).collect();
+            events.sort_by_key(|(path, _)| *path);
+            assert_eq!(
+                events,
+                [
+                    ("/ws/a.jl", FileChangeType::CHANGED),
+                    ("/ws/b.jl", FileChangeType::CHANGED),
+                    ("/ws/c.jl", FileChangeType::CREATED),
+                ]
+            );
+        }
+        state.flush_file_events();
+        assert!(state.pending_file_events.lock().is_empty());
+        // The next event after a flush schedules a new one.
+        assert!(state.queue_file_events(vec![(uri("a.jl"), FileChangeType::CHANGED)]));
+    }
+
+    #[test]
+    fn watched_file_changes_follow_the_disk_unless_open() {
+        let root = std::env::temp_dir().join(format!("parsec-changed-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let closed = root.join("closed.jl");
+        let open = root.join("open.jl");
+        fs::write(&closed, "before() = 1\n").unwrap();
+        fs::write(&open, "on_disk() = 1\n").unwrap();
+        let closed_uri = Url::from_file_path(&closed).unwrap();
+        let open_uri = Url::from_file_path(&open).unwrap();
+
+        let state = ServerState::default();
+        state.reload_from_disk(&closed);
+        state.open_doc(open_uri.to_string(), "in_editor() = 1\n".into());
+        state.reindex_doc(open_uri.as_str());
+
+        fs::write(&closed, "after() = 1\n").unwrap();
+        fs::remove_file(&open).unwrap();
+        state.queue_file_events(vec![
+            (closed_uri.clone(), FileChangeType::CHANGED),
+            (open_uri.clone(), FileChangeType::DELETED),
+        ]);
+        state.flush_file_events();
+
+        assert!(state.symbols.search_exact("before", &[]).is_empty());
+        assert_eq!(state.symbols.search_exact("after", &[]).len(), 1);
+        // The editor's buffer outlives the file on disk.
+        assert_eq!(state.symbols.search_exact("in_editor", &[]).len(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    // A synthetic package of 3000 files, about 9 KB each.
+    fn write_bench_package(root: &Path) {
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        for f in 0..3000 {
+            let mut text = format!("module M{f}\n\n");
+            for i in 0..40 {
+                text.push_str(&format!(
+                    "\"\"\"\n    f{i}(x, y)\n\nCombine `x` and `y`.\n\"\"\"\nfunction f{i}(x::Vector{{Float64}}, y; scale = 2)\n    s = zero(eltype(x))\n    for v in x\n        s += v * y * scale\n    end\n    return s\nend\n\nstruct S{i}\n    a::Int\n    b::String\nend\n\n"
+                ));
+            }
+            text.push_str("end\n");
+            fs::write(root.join("src").join(format!("m{f}.jl")), text).unwrap();
+        }
+    }
+
+    // cargo test --release -- --ignored --nocapture bench_index_workspace
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_index_workspace() {
+        let root = std::env::temp_dir().join(format!("parsec-bench-{}", std::process::id()));
+        write_bench_package(&root);
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        // index.maxThreads; 0 means one thread per core. The first run is the
+        // serial baseline the others are compared against.
+        let mut serial = None;
+        for threads in [1, 4, 0] {
+            let state = ServerState::default();
+            state.set_use_cache(false);
+            state.update_config(ParsecConfig {
+                max_indexing_threads: threads,
+                ..ParsecConfig::default()
+            });
+            state.set_roots(vec![Url::from_file_path(&root).unwrap()]);
+            let t = Instant::now();
+            state.start_indexer(root.clone(), None).await.unwrap();
+            let elapsed = t.elapsed();
+            assert_eq!(state.symbols.doc_uris().len(), 3000);
+            let serial = *serial.get_or_insert(elapsed);
+            println!(
+                "indexed 3000 files in {elapsed:?} with maxThreads={threads} on {cores} core(s), \
+                 {:.2}x the maxThreads=1 speed",
+                serial.as_secs_f64() / elapsed.as_secs_f64()
+            );
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
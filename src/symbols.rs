@@ -1,55 +1,47 @@
 use regex::Regex;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 use std::time::Duration;
 use tower_lsp::lsp_types::{
-    DocumentSymbol, Location, Position, Range, SymbolInformation, SymbolKind, SymbolTag, Url,
+    DocumentHighlight, DocumentHighlightKind, DocumentSymbol, FoldingRange, FoldingRangeKind,
+    Hover, HoverContents, LinkedEditingRanges, Location, MarkupContent, MarkupKind,
+    ParameterInformation, ParameterLabel, Position, Range, SelectionRange, SignatureHelp,
+    SignatureInformation, SymbolInformation, SymbolKind, SymbolTag, TextEdit, Url, WorkspaceEdit,
 };
 use tracing::{debug, info, warn};
-use tree_sitter::{Node, TreeCursor};
+use tree_sitter::{Node, Query, QueryCursor, StreamingIterator, Tree, TreeCursor};
 
-use crate::state::DocState;
+use crate::index::ExtractedSymbol;
+use crate::line_index::LineIndex;
+use crate::state::{DocState, ServerState};
 
-struct LineIndex {
-    starts: Vec<usize>,
-}
-
-impl LineIndex {
-    fn new(text: &str) -> Self {
-        let mut starts = Vec::with_capacity(text.lines().count() + 1);
-        starts.push(0);
-        for (i, b) in text.as_bytes().iter().enumerate() {
-            if *b == b'\n' {
-                starts.push(i + 1);
-            }
-        }
-        Self { starts }
-    }
-
-    fn to_pos(&self, idx: usize) -> Position {
-        let i = match self.starts.binary_search(&idx) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
-        };
-        Position {
-            line: i as u32,
-            character: (idx - self.starts[i]) as u32,
-        }
+// tree-sitter-julia parses `mutable struct` as a struct_definition with a
+// leading `mutable` token, so telling it apart needs the node itself.
+pub(crate) fn kind_for_node(node: Node) -> Option<SymbolKind> {
+    if short_function_call(node)
+        .and_then(short_function_name)
+        .is_some()
+    {
+        return Some(SymbolKind::FUNCTION);
     }
-
-    fn range_of(&self, start: usize, end: usize) -> Range {
-        Range {
-            start: self.to_pos(start),
-            end: self.to_pos(end),
+    if node.kind() == "struct_definition" {
+        let mut cursor = node.walk();
+        if node.children(&mut cursor).any(|c| c.kind() == "mutable") {
+            return Some(SymbolKind::CLASS);
         }
     }
+    kind_for(node.kind())
 }
 
-fn kind_for(node_type: &str) -> Option<SymbolKind> {
+pub(crate) fn kind_for(node_type: &str) -> Option<SymbolKind> {
     match node_type {
         "module_definition" | "bare_module_definition" => Some(SymbolKind::MODULE),
-        "function_definition" | "short_function_definition" => Some(SymbolKind::FUNCTION),
+        "function_definition" => Some(SymbolKind::FUNCTION),
         "macro_definition" => Some(SymbolKind::FUNCTION),
-        "struct_definition" | "primitive_type_definition" => Some(SymbolKind::STRUCT),
+        "struct_definition" | "primitive_definition" | "primitive_type_definition" => {
+            Some(SymbolKind::STRUCT)
+        }
         "abstract_definition" => Some(SymbolKind::CLASS),
         "type_alias" => Some(SymbolKind::TYPE_PARAMETER),
         "const_statement" => Some(SymbolKind::CONSTANT),
@@ -57,6 +49,213 @@ fn kind_for(node_type: &str) -> Option<SymbolKind> {
     }
 }
 
+// There is no short_function_definition node: `f(x) = x` is an assignment
+// whose left side is a call, possibly wrapped in `::T` or `where`. Indexed
+// and field assignments (`a[i] = 1`, `obj.x = 1`) have no call there.
+pub(crate) fn short_function_call(node: Node) -> Option<Node> {
+    if node.kind() != "assignment" {
+        return None;
+    }
+    let mut lhs = node.named_child(0)?;
+    while matches!(lhs.kind(), "typed_expression" | "where_expression") {
+        lhs = lhs.named_child(0)?;
+    }
+    (lhs.kind() == "call_expression").then_some(lhs)
+}
+
+fn short_function_name(call: Node) -> Option<Node> {
+    let callee = call.named_child(0)?;
+    let named = match callee.kind() {
+        "identifier" => return Some(callee),
+        // `Base.show(io, x) = ...` extends `show`.
+        "field_expression" => callee,
+        // `(a::T)(x) = ...` makes instances of T callable; T names it.
+        "parenthesized_expression" => callee
+            .named_child(0)
+            .filter(|n| matches!(n.kind(), "typed_expression" | "unary_typed_expression"))?,
+        _ => return None,
+    };
+    let last = named.named_child(named.named_child_count().checked_sub(1)?)?;
+    (last.kind() == "identifier").then_some(last)
+}
+
+fn is_interface_stub(node: Node) -> bool {
+    if node.kind() != "function_definition" {
+        return false;
+    }
+    let count = node.named_child_count();
+    let Some(signature) = (0..count)
+        .filter_map(|i| node.named_child(i))
+        .find(|n| n.kind() == "signature")
+    else {
+        return false;
+    };
+    count == 1
+        && find_named_descendant_by(signature, &|n: &Node| {
+            matches!(n.kind(), "call_expression" | "argument_list")
+        })
+        .is_none()
+}
+
+fn docstring_for(def: Node, text: &str) -> Option<String> {
+    let prev = def.prev_named_sibling()?;
+    if prev.kind() != "string_literal" || prev.end_position().row + 1 < def.start_position().row {
+        return None;
+    }
+    let raw = &text[prev.start_byte()..prev.end_byte()];
+    let body = raw
+        .strip_prefix("\"\"\"")
+        .and_then(|r| r.strip_suffix("\"\"\""))
+        .or_else(|| raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')))?;
+    let indent = body
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = body
+        .lines()
+        .map(|l| {
+            if l.len() >= indent {
+                &l[indent..]
+            } else {
+                l.trim_start()
+            }
+        })
+        .collect();
+    Some(lines.join("\n").trim_matches('\n').to_string())
+}
+
+fn detail_for(node: Node, text: &str) -> Option<String> {
+    match node.kind() {
+        "function_definition" | "macro_definition" => {
+            let call = find_named_descendant_by(node, &|n: &Node| n.kind() == "call_expression")?;
+            let args = call_arguments(call)?;
+            // Everything after the argument list (`::ReturnType`, `where T`)
+            // belongs to the signature too.
+            let end = node
+                .child_by_field_name("signature")
+                .or_else(|| find_named_descendant_by(node, &|n: &Node| n.kind() == "signature"))
+                .map_or(args.end_byte(), |sig| sig.end_byte().max(args.end_byte()));
+            Some(text[args.start_byte()..end].to_string())
+        }
+        "assignment" => {
+            let call = short_function_call(node)?;
+            let args = call_arguments(call)?;
+            let lhs = node.named_child(0)?;
+            Some(text[args.start_byte()..lhs.end_byte()].to_string())
+        }
+        "struct_definition" => {
+            let head = find_named_descendant_by(node, &|n: &Node| n.kind() == "type_head")?;
+            let fields: Vec<&str> = struct_fields(node)
+                .into_iter()
+                .map(|(decl, _)| &text[decl.byte_range()])
+                .collect();
+            let fields = format!("{{{}}}", fields.join(", "));
+            match supertype_of(head, text) {
+                Some(sup) => Some(format!("<: {sup} {fields}")),
+                None => Some(fields),
+            }
+        }
+        "abstract_definition" => {
+            let head = find_named_descendant_by(node, &|n: &Node| n.kind() == "type_head")?;
+            supertype_of(head, text).map(|sup| format!("<: {sup}"))
+        }
+        "const_statement" => {
+            let assign = find_named_descendant_by(node, &|n: &Node| n.kind() == "assignment")?;
+            let rhs = assign.named_child(assign.named_child_count().checked_sub(1)?)?;
+            Some(truncate_detail(&text[rhs.start_byte()..rhs.end_byte()]))
+        }
+        _ => None,
+    }
+}
+
+// Field declarations of a struct body, each without its `@kwdef` default
+// (`x`, `x::T`, `x = 1`, `x::T = 1`), paired with the field's name. Inner
+// constructors are skipped.
+fn struct_fields(node: Node) -> Vec<(Node, Node)> {
+    let Some(head) = find_named_descendant_by(node, &|n: &Node| n.kind() == "type_head") else {
+        return Vec::new();
+    };
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter(|n| n.start_byte() >= head.end_byte())
+        .filter_map(|n| {
+            let decl = match n.kind() {
+                "assignment" => n.named_child(0)?,
+                _ => n,
+            };
+            let name = match decl.kind() {
+                "identifier" => decl,
+                "typed_expression" => decl.named_child(0).filter(|c| c.kind() == "identifier")?,
+                _ => return None,
+            };
+            Some((decl, name))
+        })
+        .collect()
+}
+
+// Types are matched by name alone, so parameters and module qualifiers are
+// dropped from the declared supertype.
+fn supertype_name(node: Node, text: &str) -> Option<String> {
+    if !matches!(
+        node.kind(),
+        "struct_definition" | "abstract_definition" | "primitive_definition"
+    ) {
+        return None;
+    }
+    let head = find_named_descendant_by(node, &|n: &Node| n.kind() == "type_head")?;
+    let sup = supertype_of(head, text)?;
+    let base = sup.split('{').next()?.trim();
+    Some(base.rsplit('.').next()?.to_string())
+}
+
+fn supertype_of<'a>(head: Node, text: &'a str) -> Option<&'a str> {
+    let sub = head
+        .named_child(0)
+        .filter(|n| n.kind() == "binary_expression")?;
+    let op = sub.child(1)?;
+    if &text[op.start_byte()..op.end_byte()] != "<:" {
+        return None;
+    }
+    let sup = sub.named_child(sub.named_child_count().checked_sub(1)?)?;
+    Some(&text[sup.start_byte()..sup.end_byte()])
+}
+
+fn truncate_detail(s: &str) -> String {
+    const MAX: usize = 60;
+    let first = s.lines().next().unwrap_or_default();
+    if first.chars().count() <= MAX && first.len() == s.len() {
+        return first.to_string();
+    }
+    let cut: String = first.chars().take(MAX).collect();
+    format!("{cut}…")
+}
+
+pub(crate) fn definition_node_at(root: Node, offset: usize) -> Option<Node> {
+    find_named_descendant_by(root, &|n: &Node| {
+        n.start_byte() == offset && (kind_for(n.kind()).is_some() || n.kind() == "assignment")
+    })
+}
+
+fn is_foldable_kind(node_type: &str) -> bool {
+    matches!(
+        node_type,
+        "function_definition"
+            | "module_definition"
+            | "struct_definition"
+            | "abstract_definition"
+            | "macro_definition"
+            | "compound_statement"
+            | "quote_expression"
+            | "quote_statement"
+            | "let_statement"
+            | "for_statement"
+            | "while_statement"
+            | "if_statement"
+    )
+}
+
 fn is_name_kind(k: &str) -> bool {
     matches!(
         k,
@@ -70,10 +269,11 @@ fn is_name_kind(k: &str) -> bool {
     )
 }
 
-fn find_named_descendant_by<'a, F>(start: Node<'a>, pred: &F) -> Option<Node<'a>>
-where
-    F: Fn(&Node<'a>) -> bool,
-{
+// Pre-order, so the first match is the outermost and leftmost one.
+fn find_named_descendant_by<'a>(
+    start: Node<'a>,
+    mut pred: impl FnMut(&Node<'a>) -> bool,
+) -> Option<Node<'a>> {
     let mut stack = Vec::with_capacity(16);
     stack.push(start);
     while let Some(n) = stack.pop() {
@@ -90,23 +290,86 @@ where
     None
 }
 
-fn name_node<'a>(node: Node<'a>) -> Option<Node<'a>> {
+pub(crate) fn collect_named_descendants_by<'a, F>(start: Node<'a>, pred: &F) -> Vec<Node<'a>>
+where
+    F: Fn(&Node<'a>) -> bool,
+{
+    let mut out = Vec::new();
+    find_named_descendant_by(start, |n: &Node<'a>| {
+        if pred(n) {
+            out.push(*n);
+        }
+        false
+    });
+    out
+}
+
+pub(crate) fn name_node<'a>(node: Node<'a>) -> Option<Node<'a>> {
+    if node.kind() == "assignment" {
+        return short_function_call(node).and_then(short_function_name);
+    }
     if let Some(n) = node.child_by_field_name("name") {
         return Some(n);
     }
-    if let Some(n) = node.child_by_field_name("left") {
-        if let Some(found) = find_named_descendant_by(n, &|m: &Node<'a>| is_name_kind(m.kind())) {
-            return Some(found);
-        }
+    if let Some(n) = node.child_by_field_name("left")
+        && let Some(found) = find_named_descendant_by(n, &|m: &Node<'a>| is_name_kind(m.kind()))
+    {
+        return Some(found);
     }
-    if let Some(n) = node.child_by_field_name("signature") {
-        if let Some(found) = find_named_descendant_by(n, &|m: &Node<'a>| is_name_kind(m.kind())) {
-            return Some(found);
-        }
+    if let Some(n) = node.child_by_field_name("signature")
+        && let Some(found) = find_named_descendant_by(n, &|m: &Node<'a>| is_name_kind(m.kind()))
+    {
+        return Some(found);
     }
     find_named_descendant_by(node, &|m: &Node<'a>| is_name_kind(m.kind()))
 }
 
+// `module A.B.C` is not valid Julia, but the grammar accepts it as `A`, an
+// ERROR holding the dot, then `B.C`. Returns where the whole path ends and
+// its last segment, which is the name children are scoped under.
+fn module_path<'a>(node: Node<'a>, name: Node<'a>) -> (usize, Node<'a>) {
+    let mut end = name.end_byte();
+    let mut leaf = name;
+    if !matches!(node.kind(), "module_definition" | "bare_module_definition") {
+        return (end, leaf);
+    }
+    let mut next = name.next_sibling();
+    while let Some(dot) = next
+        && dot.kind() == "ERROR"
+        && dot.start_byte() == end
+        && dot.child_count() == 1
+        && dot.child(0).is_some_and(|c| c.kind() == ".")
+        && let Some(seg) = dot.next_sibling()
+        && seg.start_byte() == dot.end_byte()
+        && matches!(seg.kind(), "identifier" | "field_expression")
+    {
+        end = seg.end_byte();
+        leaf = seg
+            .named_child(seg.named_child_count().saturating_sub(1))
+            .filter(|_| seg.kind() == "field_expression")
+            .unwrap_or(seg);
+        next = seg.next_sibling();
+    }
+    (end, leaf)
+}
+
+// The name a definition's children are scoped under: the last segment of a
+// dotted module path, otherwise the name itself.
+pub(crate) fn scope_name_node<'a>(node: Node<'a>) -> Option<Node<'a>> {
+    let name = name_node(node)?;
+    Some(module_path(node, name).1)
+}
+
+// Macros are listed as they are invoked, `@name`, so outline labels and
+// fuzzy searches match call sites.
+fn symbol_label(node: Node, name: &str) -> String {
+    if node.kind() == "macro_definition" {
+        format!("@{name}")
+    } else {
+        name.to_string()
+    }
+}
+
 struct Pending {
     start: usize,
     end: usize,
@@ -115,6 +378,7 @@ struct Pending {
 
 fn make_document_symbol(
     name: String,
+    detail: Option<String>,
     kind: SymbolKind,
     range: Range,
     selection_range: Range,
@@ -123,7 +387,7 @@ fn make_document_symbol(
     {
         DocumentSymbol {
             name,
-            detail: None,
+            detail,
             kind,
             tags: None::<Vec<SymbolTag>>,
             deprecated: None,
@@ -140,10 +404,10 @@ pub fn extract_document_symbols_with_cache(
     min_delay: Duration,
 ) -> Vec<DocumentSymbol> {
     doc.parse_with_debounce(lang, min_delay);
-    let text = doc.text();
+    let (tree, text) = doc.parsed_snapshot();
     let idx = LineIndex::new(&text);
     let mut out: Vec<Pending> = Vec::new();
-    if let Some(tree) = doc.current_tree() {
+    if let Some(tree) = &tree {
         info!(
             "ts tree: bytes={} root_kind={}",
             text.len(),
@@ -154,6 +418,7 @@ pub fn extract_document_symbols_with_cache(
     } else {
         warn!("no tree after parse");
     }
+    synthesize_enum_document_symbols(&text, tree.as_ref(), &idx, &mut out);
     out.sort_by(|a, b| match a.start.cmp(&b.start) {
         Ordering::Equal => a.end.cmp(&b.end),
         x => x,
@@ -199,90 +464,848 @@ pub fn extract_workspace_symbols_with_cache(
     lang: &tree_sitter::Language,
     min_delay: Duration,
     uri: &Url,
-) -> Vec<SymbolInformation> {
+) -> Vec<ExtractedSymbol> {
     doc.parse_with_debounce(lang, min_delay);
-    let text = doc.text();
+    let (tree, text) = doc.parsed_snapshot();
     let idx = LineIndex::new(&text);
-    let mut out: Vec<SymbolInformation> = Vec::new();
-    if let Some(tree) = doc.current_tree() {
+    let mut out: Vec<ExtractedSymbol> = Vec::new();
+    if let Some(tree) = &tree {
         let mut cursor = tree.walk();
-        collect_workspace_symbols(&text, &idx, &mut cursor, uri, &mut out);
+        collect_workspace_symbols(&text, &idx, &mut cursor, uri, &mut Vec::new(), &mut out);
     }
-    out.extend(synthesize_macro_symbols(&text, uri));
-    out.extend(synthesize_shorthand_symbols(&text, uri));
+    // A macro-wrapped definition the tree walk already found on the same
+    // line is not repeated.
+    let found: HashSet<(String, u32)> = out
+        .iter()
+        .map(|s| (s.info.name.clone(), s.info.location.range.start.line))
+        .collect();
+    let macros = synthesize_macro_symbols(&text, uri)
+        .into_iter()
+        .filter(|s| !found.contains(&(s.name.clone(), s.location.range.start.line)));
+    let synthesized = macros
+        .chain(synthesize_shorthand_symbols(&text, uri))
+        .chain(synthesize_enum_symbols(&text, tree.as_ref(), &idx, uri));
+    out.extend(synthesized.map(ExtractedSymbol::from));
     out
 }
 
-fn synthesize_macro_symbols(text: &str, uri: &Url) -> Vec<SymbolInformation> {
+// The name of a module, function or struct definition, linked with the
+// places that have to repeat it: a struct's inner constructors and a
+// trailing `end # module Foo` style comment.
+pub fn linked_editing_ranges(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    pos: Position,
+) -> Option<LinkedEditingRanges> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    let tree = tree?;
+    let idx = LineIndex::new(&text);
+    let offset = idx.to_byte_offset(pos)?;
+    let ident = identifier_at(tree.root_node(), offset)?;
+    // An inner constructor's name links through its struct, so the search
+    // goes on past definitions that only have the one occurrence.
+    let mut def = ident.parent();
+    while let Some(n) = def {
+        let spans = linked_name_spans(n, &text);
+        if spans.len() > 1 && spans.contains(&ident.byte_range()) {
+            return Some(LinkedEditingRanges {
+                ranges: spans
+                    .into_iter()
+                    .map(|r| idx.range_of(r.start, r.end))
+                    .collect(),
+                word_pattern: None,
+            });
+        }
+        def = n.parent();
+    }
+    None
+}
+
+fn linked_name_spans(def: Node, text: &str) -> Vec<std::ops::Range<usize>> {
+    if !matches!(
+        def.kind(),
+        "module_definition" | "function_definition" | "struct_definition"
+    ) {
+        return Vec::new();
+    }
+    let Some(ident) = name_node(def) else {
+        return Vec::new();
+    };
+    let name = &text[ident.byte_range()];
+    let mut spans = vec![ident.byte_range()];
+    if def.kind() == "struct_definition" {
+        let mut cursor = def.walk();
+        for child in def.named_children(&mut cursor) {
+            let ctor = match child.kind() {
+                "function_definition" => name_node(child),
+                "assignment" => short_function_call(child).and_then(short_function_name),
+                _ => None,
+            };
+            if let Some(ctor) = ctor.filter(|c| &text[c.byte_range()] == name) {
+                spans.push(ctor.byte_range());
+            }
+        }
+    }
+    let Some(end) = def
+        .child(def.child_count().saturating_sub(1))
+        .filter(|e| e.kind() == "end")
+    else {
+        return spans;
+    };
+    let rest = &text[end.end_byte()..];
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    if let Some(hash) = line.find('#') {
+        let comment_start = end.end_byte() + hash;
+        let comment = &line[hash..];
+        let found = comment.match_indices(name).find(|&(i, _)| {
+            let before = comment[..i].chars().next_back();
+            let after = comment[i + name.len()..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        });
+        if let Some((i, _)) = found {
+            let start = comment_start + i;
+            spans.push(start..start + name.len());
+        }
+    }
+    spans
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '!'
+}
+
+pub fn highlight_occurrences(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    pos: Position,
+) -> Vec<DocumentHighlight> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let Some(offset) = idx.to_byte_offset(pos) else {
+        return Vec::new();
+    };
+    let root = tree.root_node();
+    let Some(target) = identifier_at(root, offset) else {
+        return Vec::new();
+    };
+    let name = &text[target.start_byte()..target.end_byte()];
+
+    let definition_names: HashSet<usize> =
+        collect_named_descendants_by(root, &|n: &Node| kind_for_node(*n).is_some())
+            .into_iter()
+            .filter_map(name_node)
+            .map(|n| n.id())
+            .collect();
+
+    let mut out: Vec<DocumentHighlight> = collect_named_descendants_by(root, &|n: &Node| {
+        n.kind() == "identifier" && &text[n.start_byte()..n.end_byte()] == name
+    })
+    .into_iter()
+    .map(|n| DocumentHighlight {
+        range: idx.range_of(n.start_byte(), n.end_byte()),
+        kind: Some(if definition_names.contains(&n.id()) {
+            DocumentHighlightKind::WRITE
+        } else {
+            DocumentHighlightKind::READ
+        }),
+    })
+    .collect();
+    out.sort_by_key(|h| (h.range.start.line, h.range.start.character));
+    out
+}
+
+pub fn hover_at(state: &ServerState, uri: &str, pos: Position) -> Option<Hover> {
+    let (name, range) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))?;
+    let mut sections = Vec::new();
+    let mut entries = state.symbols.search_exact(&name, &[]);
+    entries.extend(state.symbols.search_exact(&format!("@{name}"), &[]));
+    for entry in entries {
+        let Some(doc) = state.docs.get(entry.uri.as_str()) else {
+            continue;
+        };
+        doc.parse_with_debounce(&state.lang, Duration::ZERO);
+        let (Some(tree), text) = doc.parsed_snapshot() else {
+            continue;
+        };
+        let idx = LineIndex::new(&text);
+        let Some(def) = idx
+            .to_byte_offset(entry.range.start)
+            .and_then(|offset| definition_node_at(tree.root_node(), offset))
+        else {
+            continue;
+        };
+        // The indexed signature covers argument lists that wrap across
+        // lines, which the first line of the definition would cut off.
+        let header = match (&entry.signature, def.child(0), name_node(def)) {
+            (Some(sig), Some(keyword), Some(name)) if def.kind() != "assignment" => format!(
+                "{} {}{sig}",
+                &text[keyword.byte_range()],
+                &text[name.byte_range()]
+            ),
+            _ => text[def.start_byte()..def.end_byte()]
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_end()
+                .to_string(),
+        };
+        let mut section = format!("```julia\n{header}\n```");
+        if is_interface_stub(def) {
+            section.push_str("\n*interface stub*");
+        }
+        if let Some(doc) = docstring_for(def, &text) {
+            section.push_str("\n\n");
+            section.push_str(&doc);
+        }
+        sections.push(section);
+    }
+    if sections.is_empty() {
+        return None;
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: sections.join("\n\n---\n\n"),
+        }),
+        range: Some(range),
+    })
+}
+
+fn identifier_at(root: Node, offset: usize) -> Option<Node> {
+    let node = find_identifier_at_position(root, offset)?;
+    if node.kind() == "identifier" {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .find(|n| n.kind() == "identifier")
+}
+
+// The deepest named node containing `byte_offset`. On the boundary between
+// two siblings the right one wins, so `f|(x)` lands on the argument list;
+// the left one is only used when nothing starts there, as at the end of a
+// line.
+pub(crate) fn find_node_at_position<'a>(root: Node<'a>, byte_offset: usize) -> Option<Node<'a>> {
+    if byte_offset < root.start_byte() || byte_offset > root.end_byte() {
+        return None;
+    }
+    let mut node = root;
+    loop {
+        let mut cursor = node.walk();
+        let mut left = None;
+        let mut next = None;
+        for child in node.named_children(&mut cursor) {
+            if child.start_byte() <= byte_offset && byte_offset < child.end_byte() {
+                next = Some(child);
+                break;
+            }
+            if child.end_byte() == byte_offset {
+                left = Some(child);
+            }
+        }
+        match next.or(left) {
+            Some(child) => node = child,
+            None => return Some(node),
+        }
+    }
+}
+
+// The innermost definition (function, type, module, const...) containing
+// `byte_offset`, as kind_for_node sees it.
+pub(crate) fn find_enclosing_definition(root: Node<'_>, byte_offset: usize) -> Option<Node<'_>> {
+    let mut cur = find_node_at_position(root, byte_offset);
+    while let Some(n) = cur {
+        if kind_for_node(n).is_some() {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    None
+}
+
+pub(crate) fn find_enclosing_module(root: Node<'_>, byte_offset: usize) -> Option<Node<'_>> {
+    let mut cur = find_enclosing_definition(root, byte_offset);
+    while let Some(n) = cur {
+        if matches!(n.kind(), "module_definition" | "bare_module_definition") {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    None
+}
+
+// An identifier or macro name (`@foo`) at `byte_offset`, including a cursor
+// just past the name as in `foo|(x)`.
+pub(crate) fn find_identifier_at_position<'a>(
+    root: Node<'a>,
+    byte_offset: usize,
+) -> Option<Node<'a>> {
+    let mut cur = find_node_at_position(root, byte_offset);
+    while let Some(n) = cur {
+        if matches!(n.kind(), "identifier" | "macro_identifier") {
+            return Some(n);
+        }
+        cur = n.parent();
+    }
+    let before = find_node_at_position(root, byte_offset.checked_sub(1)?)?;
+    (before.kind() == "identifier" && before.end_byte() == byte_offset).then_some(before)
+}
+
+pub fn identifier_at_position(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    pos: Position,
+) -> Option<(String, Range)> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return None;
+    };
+    let idx = LineIndex::new(&text);
+    let offset = idx.to_byte_offset(pos)?;
+    let node = identifier_at(tree.root_node(), offset)?;
+    Some((
+        text[node.start_byte()..node.end_byte()].to_string(),
+        idx.range_of(node.start_byte(), node.end_byte()),
+    ))
+}
+
+// Callees are matched by name alone: `f(x)`, `Mod.f(x)` and `f.(x)` are all
+// calls to `f`.
+const CALLERS_QUERY: &str = r#"
+(call_expression . (identifier) @callee)
+(call_expression . (field_expression (identifier) @callee .))
+(broadcast_call_expression . (identifier) @callee)
+"#;
+
+fn callers_query(lang: &tree_sitter::Language) -> &'static Query {
+    static QUERY: OnceLock<Query> = OnceLock::new();
+    QUERY.get_or_init(|| Query::new(lang, CALLERS_QUERY).expect("callers query compiles"))
+}
+
+// Ranges of the callee names in every call to `name`, skipping the `f(x)`
+// heads of definitions.
+pub fn find_callers(name: &str, doc: &DocState, lang: &tree_sitter::Language) -> Vec<Range> {
+    if !doc.text().contains(name) {
+        return Vec::new();
+    }
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(callers_query(lang), tree.root_node(), text.as_bytes());
     let mut out = Vec::new();
-    let re_userplot = Regex::new(r"(?m)^\s*@userplot\s+([A-Za-z][A-Za-z0-9_]*)").unwrap();
-    for cap in re_userplot.captures_iter(text) {
-        let name = cap.get(1).unwrap().as_str().to_string();
-        let (line, col) = line_col_of_match(text, cap.get(1).unwrap().start());
-        #[allow(deprecated)]
-        out.push(SymbolInformation {
-            name,
-            kind: SymbolKind::FUNCTION,
-            location: Location {
-                uri: uri.clone(),
-                range: Range {
-                    start: Position {
-                        line,
-                        character: col,
-                    },
-                    end: Position {
-                        line,
-                        character: col + 1,
-                    },
-                },
-            },
-            container_name: None,
-            deprecated: None,
-            tags: None,
+    while let Some(m) = matches.next() {
+        for cap in m.captures {
+            let callee = cap.node;
+            if &text[callee.byte_range()] != name {
+                continue;
+            }
+            let mut call = callee.parent();
+            while let Some(n) = call
+                && !matches!(n.kind(), "call_expression" | "broadcast_call_expression")
+            {
+                call = n.parent();
+            }
+            if call.is_some_and(is_definition_head) {
+                continue;
+            }
+            out.push(idx.range_of(callee.start_byte(), callee.end_byte()));
+        }
+    }
+    out
+}
+
+// Whether a call expression is the `f(x)` of `function f(x)` or `f(x) = ...`
+// rather than a call.
+pub(crate) fn is_definition_head(call: Node) -> bool {
+    let mut child = call;
+    let mut cur = call.parent();
+    while let Some(parent) = cur {
+        match parent.kind() {
+            "signature" => return true,
+            "typed_expression" | "where_expression" => {}
+            "assignment" => {
+                return parent
+                    .named_child(0)
+                    .is_some_and(|lhs| lhs.id() == child.id());
+            }
+            _ => return false,
+        }
+        child = parent;
+        cur = parent.parent();
+    }
+    false
+}
+
+pub fn identifier_occurrences(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    name: &str,
+) -> Vec<Range> {
+    if !doc.text().contains(name) {
+        return Vec::new();
+    }
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    collect_named_descendants_by(tree.root_node(), &|n: &Node| {
+        n.kind() == "identifier" && &text[n.start_byte()..n.end_byte()] == name
+    })
+    .into_iter()
+    .map(|n| idx.range_of(n.start_byte(), n.end_byte()))
+    .collect()
+}
+
+fn is_reserved_word(name: &str) -> bool {
+    matches!(
+        name,
+        "function"
+            | "end"
+            | "module"
+            | "baremodule"
+            | "struct"
+            | "mutable"
+            | "abstract"
+            | "primitive"
+            | "type"
+            | "macro"
+            | "if"
+            | "elseif"
+            | "else"
+            | "for"
+            | "in"
+            | "isa"
+            | "while"
+            | "begin"
+            | "let"
+            | "quote"
+            | "return"
+            | "try"
+            | "catch"
+            | "finally"
+            | "do"
+            | "const"
+            | "global"
+            | "local"
+            | "import"
+            | "using"
+            | "export"
+            | "public"
+            | "where"
+            | "break"
+            | "continue"
+            | "true"
+            | "false"
+            | "nothing"
+            | "missing"
+    )
+}
+
+pub fn prepare_rename(
+    state: &ServerState,
+    uri: &str,
+    pos: Position,
+) -> anyhow::Result<(String, Range)> {
+    let (name, range) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))
+        .ok_or_else(|| anyhow::anyhow!("no identifier at position"))?;
+    if is_reserved_word(&name) {
+        anyhow::bail!("`{name}` is a reserved word and cannot be renamed");
+    }
+    let definitions = state.symbols.search_exact(&name, &[]);
+    if !definitions.is_empty() {
+        let roots = state.roots();
+        let in_workspace = definitions
+            .iter()
+            .any(|e| e.uri.as_str() == uri || (!roots.is_empty() && e.within_roots(&roots)));
+        if !in_workspace {
+            anyhow::bail!("`{name}` is defined outside the workspace and cannot be renamed");
+        }
+    }
+    Ok((name, range))
+}
+
+pub fn rename_symbol(
+    state: &ServerState,
+    uri: &str,
+    pos: Position,
+    new_name: &str,
+) -> anyhow::Result<WorkspaceEdit> {
+    let valid = Regex::new(r"^[A-Za-zα-ωΑ-Ω_][A-Za-zα-ωΑ-Ω0-9_!]*$").unwrap();
+    if !valid.is_match(new_name) || is_reserved_word(new_name) {
+        anyhow::bail!("`{new_name}` is not a valid Julia identifier");
+    }
+    let (name, _) = prepare_rename(state, uri, pos)?;
+    // Without scope analysis every identifier with the same text is a match,
+    // so the rename stays inside the current document rather than touching
+    // unrelated bindings that merely share a name in other files.
+    let doc = state
+        .docs
+        .get(uri)
+        .ok_or_else(|| anyhow::anyhow!("no document state for {uri}"))?;
+    let mut ranges = identifier_occurrences(&doc, &state.lang, &name);
+    drop(doc);
+    ranges.sort_by_key(|r| (r.start.line, r.start.character));
+    ranges.dedup();
+    let url = Url::parse(uri)?;
+    let edits = ranges
+        .into_iter()
+        .map(|range| TextEdit {
+            range,
+            new_text: new_name.to_string(),
+        })
+        .collect();
+    let changes = HashMap::from([(url, edits)]);
+    Ok(WorkspaceEdit {
+        changes: Some(changes),
+        document_changes: None,
+        change_annotations: None,
+    })
+}
+
+pub fn folding_ranges(doc: &DocState, lang: &tree_sitter::Language) -> Vec<FoldingRange> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let root = tree.root_node();
+    let mut out = Vec::new();
+
+    for node in collect_named_descendants_by(root, &|n: &Node| is_foldable_kind(n.kind())) {
+        let start_line = idx.to_pos(node.start_byte()).line;
+        let end_line = idx.to_pos(node.end_byte()).line.saturating_sub(1);
+        if end_line > start_line {
+            out.push(FoldingRange {
+                start_line,
+                start_character: None,
+                end_line,
+                end_character: None,
+                kind: Some(FoldingRangeKind::Region),
+                collapsed_text: None,
+            });
+        }
+    }
+
+    let mut run: Option<(u32, u32)> = None;
+    let comments = collect_named_descendants_by(root, &|n: &Node| {
+        matches!(n.kind(), "line_comment" | "block_comment")
+    });
+    for node in comments {
+        let pos = idx.to_pos(node.start_byte());
+        if node.kind() == "block_comment" {
+            push_comment_fold(&mut out, pos.line, idx.to_pos(node.end_byte()).line);
+            continue;
+        }
+        let line_start = idx.line_start(pos.line as usize);
+        if !text[line_start..node.start_byte()].trim().is_empty() {
+            continue;
+        }
+        let line = pos.line;
+        run = match run {
+            Some((start, end)) if line == end + 1 => Some((start, line)),
+            Some((start, end)) => {
+                push_comment_fold(&mut out, start, end);
+                Some((line, line))
+            }
+            None => Some((line, line)),
+        };
+    }
+    if let Some((start, end)) = run {
+        push_comment_fold(&mut out, start, end);
+    }
+
+    out.sort_by_key(|r| (r.start_line, r.end_line));
+    out
+}
+
+fn push_comment_fold(out: &mut Vec<FoldingRange>, start_line: u32, end_line: u32) {
+    if end_line > start_line {
+        out.push(FoldingRange {
+            start_line,
+            start_character: None,
+            end_line,
+            end_character: None,
+            kind: Some(FoldingRangeKind::Comment),
+            collapsed_text: None,
         });
     }
-    let re_recipe_fun =
-        Regex::new(r"(?m)^\s*@recipe\s+function\s+([A-Za-z][A-Za-z0-9_]*)\b").unwrap();
-    for cap in re_recipe_fun.captures_iter(text) {
-        let name = cap.get(1).unwrap().as_str().to_string();
-        let (line, col) = line_col_of_match(text, cap.get(1).unwrap().start());
-        #[allow(deprecated)]
-        out.push(SymbolInformation {
-            name,
-            kind: SymbolKind::FUNCTION,
-            location: Location {
-                uri: uri.clone(),
-                range: Range {
-                    start: Position {
-                        line,
-                        character: col,
-                    },
-                    end: Position {
-                        line,
-                        character: col + 1,
-                    },
-                },
-            },
-            container_name: None,
-            deprecated: None,
-            tags: None,
+}
+
+pub fn selection_ranges_at(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    positions: &[Position],
+) -> Vec<SelectionRange> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (tree, text) = doc.parsed_snapshot();
+    let idx = LineIndex::new(&text);
+    let whole = idx.range_of(0, text.len());
+
+    positions
+        .iter()
+        .map(|&pos| {
+            let node = tree.as_ref().and_then(|tree| {
+                let offset = idx.to_byte_offset(pos)?;
+                tree.root_node().descendant_for_byte_range(offset, offset)
+            });
+            let Some(mut node) = node else {
+                return SelectionRange {
+                    range: whole,
+                    parent: None,
+                };
+            };
+            // Innermost first; ancestors that span the same text as their
+            // child would be a no-op expansion, so they are skipped.
+            let mut ranges = vec![idx.range_of(node.start_byte(), node.end_byte())];
+            while let Some(parent) = node.parent() {
+                let range = idx.range_of(parent.start_byte(), parent.end_byte());
+                if ranges.last() != Some(&range) {
+                    ranges.push(range);
+                }
+                node = parent;
+            }
+            if ranges.last() != Some(&whole) {
+                ranges.push(whole);
+            }
+            let mut selection: Option<SelectionRange> = None;
+            for range in ranges.into_iter().rev() {
+                selection = Some(SelectionRange {
+                    range,
+                    parent: selection.map(Box::new),
+                });
+            }
+            selection.expect("at least one range")
+        })
+        .collect()
+}
+
+pub fn signature_help_at(state: &ServerState, uri: &str, pos: Position) -> Option<SignatureHelp> {
+    // Snapshot the call site so this document's entry is released before
+    // the definitions' documents are looked up below.
+    let (tree, text) = {
+        let doc = state.docs.get(uri)?;
+        doc.parse_with_debounce(&state.lang, Duration::ZERO);
+        match doc.parsed_snapshot() {
+            (Some(tree), text) => (tree, text),
+            (None, _) => return None,
+        }
+    };
+    let idx = LineIndex::new(&text);
+    let offset = idx.to_byte_offset(pos)?;
+
+    let call = collect_named_descendants_by(tree.root_node(), &|n: &Node| {
+        n.kind() == "call_expression"
+            && call_arguments(*n).is_some_and(|args| cursor_in_arguments(args, offset))
+    })
+    .into_iter()
+    .min_by_key(|n| n.end_byte() - n.start_byte())?;
+    let callee = call.named_child(0)?;
+    let callee_name = match callee.kind() {
+        "identifier" => &text[callee.start_byte()..callee.end_byte()],
+        "field_expression" => {
+            let leaf = callee.named_child(callee.named_child_count().checked_sub(1)?)?;
+            &text[leaf.start_byte()..leaf.end_byte()]
+        }
+        _ => return None,
+    };
+    let args = call_arguments(call)?;
+    let (positional_sep, keyword_sep, in_keywords) = count_separators(args, offset);
+
+    let mut signatures = Vec::new();
+    let mut active_parameters = Vec::new();
+    for entry in state.symbols.search_exact(callee_name, &[]) {
+        if entry.kind != SymbolKind::FUNCTION {
+            continue;
+        }
+        let found = state
+            .docs
+            .get(entry.uri.as_str())
+            .and_then(|d| signature_of_definition(&d, &state.lang, entry.range.start));
+        let Some((label, params, n_positional)) = found else {
+            continue;
+        };
+        let active = if in_keywords {
+            n_positional + keyword_sep
+        } else {
+            positional_sep
+        };
+        let parameters = params
+            .into_iter()
+            .map(|(start, end)| ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start as u32, end as u32]),
+                documentation: None,
+            })
+            .collect::<Vec<_>>();
+        active_parameters.push(active as u32);
+        signatures.push(SignatureInformation {
+            label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter: Some(active as u32),
         });
     }
-    out
+    if signatures.is_empty() {
+        return None;
+    }
+    Some(SignatureHelp {
+        active_parameter: active_parameters.first().copied(),
+        signatures,
+        active_signature: Some(0),
+    })
 }
 
-fn synthesize_shorthand_symbols(text: &str, uri: &Url) -> Vec<SymbolInformation> {
+fn call_arguments(call: Node) -> Option<Node> {
+    let count = call.named_child_count();
+    (0..count)
+        .filter_map(|i| call.named_child(i))
+        .find(|n| n.kind() == "argument_list")
+}
+
+fn cursor_in_arguments(args: Node, offset: usize) -> bool {
+    if offset <= args.start_byte() {
+        return false;
+    }
+    let closed = args
+        .child(args.child_count().saturating_sub(1))
+        .is_some_and(|c| c.kind() == ")" && !c.is_missing());
+    !closed || offset < args.end_byte()
+}
+
+fn count_separators(args: Node, offset: usize) -> (usize, usize, bool) {
+    let mut positional = 0usize;
+    let mut keyword = 0usize;
+    let mut in_keywords = false;
+    for i in 0..args.child_count() {
+        let Some(ch) = args.child(i) else {
+            continue;
+        };
+        if ch.start_byte() >= offset {
+            break;
+        }
+        match ch.kind() {
+            ";" => in_keywords = true,
+            "," if in_keywords => keyword += 1,
+            "," => positional += 1,
+            _ => {}
+        }
+    }
+    (positional, keyword, in_keywords)
+}
+
+type ParsedSignature = (String, Vec<(usize, usize)>, usize);
+
+fn signature_of_definition(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    start: Position,
+) -> Option<ParsedSignature> {
+    doc.parse_with_debounce(lang, Duration::ZERO);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return None;
+    };
+    let idx = LineIndex::new(&text);
+    let offset = idx.to_byte_offset(start)?;
+    let def = definition_node_at(tree.root_node(), offset)
+        .filter(|n| matches!(n.kind(), "function_definition" | "assignment"))?;
+    let call = find_named_descendant_by(def, &|n: &Node| n.kind() == "call_expression")?;
+    let callee = call.named_child(0)?;
+    let args = call_arguments(call)?;
+
+    let mut label = text[callee.start_byte()..callee.end_byte()].to_string();
+    label.push('(');
+    let mut params = Vec::new();
+    let mut n_positional = 0usize;
+    let mut in_keywords = false;
+    let mut first = true;
+    for i in 0..args.child_count() {
+        let Some(ch) = args.child(i) else {
+            continue;
+        };
+        if ch.kind() == ";" {
+            in_keywords = true;
+            label.push_str("; ");
+            first = true;
+            continue;
+        }
+        if !ch.is_named() {
+            continue;
+        }
+        if !first {
+            label.push_str(", ");
+        }
+        first = false;
+        let start = label.len();
+        label.push_str(&text[ch.start_byte()..ch.end_byte()]);
+        params.push((start, label.len()));
+        if !in_keywords {
+            n_positional += 1;
+        }
+    }
+    label.push(')');
+    Some((label, params, n_positional))
+}
+
+pub fn extract_identifier_occurrences(
+    doc: &DocState,
+    lang: &tree_sitter::Language,
+    min_delay: Duration,
+) -> Vec<(String, Range, bool)> {
+    doc.parse_with_debounce(lang, min_delay);
+    let (Some(tree), text) = doc.parsed_snapshot() else {
+        return Vec::new();
+    };
+    let idx = LineIndex::new(&text);
+    let root = tree.root_node();
+    let definition_names: HashSet<usize> =
+        collect_named_descendants_by(root, &|n: &Node| kind_for_node(*n).is_some())
+            .into_iter()
+            .filter_map(name_node)
+            .map(|n| n.id())
+            .collect();
+    collect_named_descendants_by(root, &|n: &Node| n.kind() == "identifier")
+        .into_iter()
+        .map(|n| {
+            (
+                text[n.start_byte()..n.end_byte()].to_string(),
+                idx.range_of(n.start_byte(), n.end_byte()),
+                definition_names.contains(&n.id()),
+            )
+        })
+        .collect()
+}
+
+// Macro forms that define a function by name. The tree walk already sees
+// `@generated function` and `@doc "..." f(x) = ...` when they parse, so
+// these patterns matter for files with syntax errors and for a bare
+// `@doc "..." f`, which documents `f` without defining it there.
+const MACRO_SYMBOL_PATTERNS: &[&str] = &[
+    r"(?m)^\s*@userplot\s+([A-Za-z][A-Za-z0-9_]*)",
+    r"(?m)^\s*@recipe\s+function\s+([A-Za-z][A-Za-z0-9_]*)\b",
+    r"(?m)^\s*@generated\s+function\s+([A-Za-z_][A-Za-z0-9_!]*)",
+    r#"(?m)^\s*@doc\s+(?:"""[^"]*"""|"[^"]*"|`[^`]*`)\s+([A-Za-z_][A-Za-z0-9_!]*)"#,
+];
+
+fn synthesize_macro_symbols(text: &str, uri: &Url) -> Vec<SymbolInformation> {
     let mut out = Vec::new();
-    let re_anchor = Regex::new(r"(?m)@shorthands").unwrap();
-    let re_name = Regex::new(r"[:]?([A-Za-z][A-Za-z0-9_]*!?)[\s,\]\)]").unwrap();
-    for a in re_anchor.find_iter(text) {
-        let start = a.start();
-        let end = text.len().min(start + 600);
-        let window = &text[start..end];
-        for cap in re_name.captures_iter(window) {
-            let m = cap.get(1).unwrap();
-            let name = m.as_str().to_string();
-            let (line, col) = line_col_of_match(text, start + m.start());
+    for pattern in MACRO_SYMBOL_PATTERNS {
+        let re = Regex::new(pattern).unwrap();
+        for cap in re.captures_iter(text) {
+            let name = cap.get(1).unwrap().as_str().to_string();
+            let (line, col) = line_col_of_match(text, cap.get(1).unwrap().start());
             #[allow(deprecated)]
             out.push(SymbolInformation {
                 name,
@@ -309,18 +1332,259 @@ fn synthesize_shorthand_symbols(text: &str, uri: &Url) -> Vec<SymbolInformation>
     out
 }
 
-fn line_col_of_match(text: &str, byte_idx: usize) -> (u32, u32) {
-    let mut line: u32 = 0;
-    let mut last = 0usize;
-    for (i, _l) in text.match_indices('\n') {
-        if i >= byte_idx {
-            break;
+// Julia's reserved words; none of them can name a plotting function.
+const JULIA_KEYWORDS: &[&str] = &[
+    "baremodule",
+    "begin",
+    "break",
+    "catch",
+    "const",
+    "continue",
+    "do",
+    "else",
+    "elseif",
+    "end",
+    "export",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "global",
+    "if",
+    "import",
+    "let",
+    "local",
+    "macro",
+    "module",
+    "quote",
+    "return",
+    "struct",
+    "true",
+    "try",
+    "using",
+    "while",
+];
+
+fn synthesize_shorthand_symbols(text: &str, uri: &Url) -> Vec<SymbolInformation> {
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    let re_anchor = Regex::new(r"@shorthands\b").unwrap();
+    let re_name = Regex::new(r":?([A-Za-z][A-Za-z0-9_]*!?)").unwrap();
+    let idx = LineIndex::new(text);
+    for a in re_anchor.find_iter(text) {
+        let (start, end) = shorthands_arguments(text, a.end());
+        for cap in re_name.captures_iter(&text[start..end]) {
+            let m = cap.get(1).unwrap();
+            let name = m.as_str();
+            if JULIA_KEYWORDS.contains(&name) {
+                continue;
+            }
+            let range = idx.range_of(start + m.start(), start + m.end());
+            if !seen.insert((name.to_string(), range.start.line)) {
+                continue;
+            }
+            #[allow(deprecated)]
+            out.push(SymbolInformation {
+                name: name.to_string(),
+                kind: SymbolKind::FUNCTION,
+                location: Location {
+                    uri: uri.clone(),
+                    range,
+                },
+                container_name: None,
+                deprecated: None,
+                tags: None,
+            });
+        }
+    }
+    out
+}
+
+// Byte span of the arguments of an `@shorthands` call: up to the matching
+// bracket for `@shorthands(...)`/`@shorthands [...]`, otherwise the rest of
+// the line without its comment.
+fn shorthands_arguments(text: &str, after: usize) -> (usize, usize) {
+    let rest = &text[after..];
+    let open = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    if let Some(close) = matching_close(rest.as_bytes(), open) {
+        return (after + open + 1, after + close);
+    }
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let code = &line[..line.find('#').unwrap_or(line.len())];
+    (after, after + code.len())
+}
+
+fn matching_close(bytes: &[u8], open: usize) -> Option<usize> {
+    let close = match bytes.get(open)? {
+        b'(' => b')',
+        b'[' => b']',
+        _ => return None,
+    };
+    let mut depth = 0usize;
+    for (i, &b) in bytes.iter().enumerate().skip(open) {
+        if b == bytes[open] {
+            depth += 1;
+        } else if b == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
         }
-        line += 1;
-        last = i + 1;
     }
-    let col = (byte_idx - last) as u32;
-    (line, col)
+    None
+}
+
+// Byte spans of an `@enum` declaration: the whole statement, the type name
+// and every member name.
+struct EnumDecl {
+    span: (usize, usize),
+    name: (usize, usize),
+    members: Vec<(usize, usize)>,
+}
+
+fn enum_declarations(text: &str, tree: Option<&Tree>) -> Vec<EnumDecl> {
+    let re_enum = Regex::new(
+        r"(?m)^[ \t]*@enum[ \t]+([A-Za-z_][A-Za-z0-9_]*)(?:[ \t]*::[ \t]*[A-Za-z_][A-Za-z0-9_.]*)?",
+    )
+    .unwrap();
+    let re_block = Regex::new(r"^[ \t]*begin\b").unwrap();
+    let re_paren = Regex::new(r"^[ \t]*\(").unwrap();
+    let re_end = Regex::new(r"(?m)^[ \t]*end\b").unwrap();
+    let re_member =
+        Regex::new(r"(?:^|[\s,;])([A-Za-z_][A-Za-z0-9_]*)(?:[ \t]*=[ \t]*[^\s,;#]+)?").unwrap();
+
+    let mut out = Vec::new();
+    for cap in re_enum.captures_iter(text) {
+        let whole = cap.get(0).unwrap();
+        let name = cap.get(1).unwrap();
+        let rest = &text[whole.end()..];
+        // `@enum T begin ... end` and `@enum T (a, b)` may list members over
+        // several lines, otherwise they follow the type name on the same line.
+        let (body_start, body_end, span_end) = if let Some(b) = re_block.find(rest) {
+            let body_start = whole.end() + b.end();
+            match re_end.find(&text[body_start..]) {
+                Some(e) => (body_start, body_start + e.start(), body_start + e.end()),
+                None => (body_start, text.len(), text.len()),
+            }
+        } else if let Some(p) = re_paren.find(rest) {
+            let body_start = whole.end() + p.end();
+            // The tuple node closes at the matching paren, past any calls in
+            // member values like `a = f(1)`.
+            let tuple = tree
+                .and_then(|t| {
+                    t.root_node()
+                        .descendant_for_byte_range(body_start - 1, body_start)
+                })
+                .and_then(|n| n.parent())
+                .filter(|n| n.kind() == "tuple_expression" && n.start_byte() == body_start - 1);
+            match tuple {
+                Some(t) => (body_start, t.end_byte() - 1, t.end_byte()),
+                None => (body_start, text.len(), text.len()),
+            }
+        } else {
+            let eol = rest.find('\n').map_or(text.len(), |i| whole.end() + i);
+            (whole.end(), eol, eol)
+        };
+
+        let mut members = Vec::new();
+        let mut line_start = body_start;
+        for line in text[body_start..body_end].split_inclusive('\n') {
+            let code = line.split('#').next().unwrap_or_default();
+            for m in re_member.captures_iter(code) {
+                let m = m.get(1).unwrap();
+                members.push((line_start + m.start(), line_start + m.end()));
+            }
+            line_start += line.len();
+        }
+        out.push(EnumDecl {
+            span: (
+                whole.start() + whole.as_str().find('@').unwrap_or(0),
+                span_end,
+            ),
+            name: (name.start(), name.end()),
+            members,
+        });
+    }
+    out
+}
+
+fn synthesize_enum_symbols(
+    text: &str,
+    tree: Option<&Tree>,
+    idx: &LineIndex,
+    uri: &Url,
+) -> Vec<SymbolInformation> {
+    let mut out = Vec::new();
+    for decl in enum_declarations(text, tree) {
+        let enum_name = &text[decl.name.0..decl.name.1];
+        #[allow(deprecated)]
+        out.push(SymbolInformation {
+            name: enum_name.to_string(),
+            kind: SymbolKind::ENUM,
+            location: Location {
+                uri: uri.clone(),
+                range: idx.range_of(decl.name.0, decl.name.1),
+            },
+            container_name: None,
+            deprecated: None,
+            tags: None,
+        });
+        for (start, end) in decl.members {
+            #[allow(deprecated)]
+            out.push(SymbolInformation {
+                name: text[start..end].to_string(),
+                kind: SymbolKind::ENUM_MEMBER,
+                location: Location {
+                    uri: uri.clone(),
+                    range: idx.range_of(start, end),
+                },
+                container_name: Some(enum_name.to_string()),
+                deprecated: None,
+                tags: None,
+            });
+        }
+    }
+    out
+}
+
+fn synthesize_enum_document_symbols(
+    text: &str,
+    tree: Option<&Tree>,
+    idx: &LineIndex,
+    out: &mut Vec<Pending>,
+) {
+    for decl in enum_declarations(text, tree) {
+        out.push(Pending {
+            start: decl.span.0,
+            end: decl.span.1,
+            sym: make_document_symbol(
+                text[decl.name.0..decl.name.1].to_string(),
+                None,
+                SymbolKind::ENUM,
+                idx.range_of(decl.span.0, decl.span.1),
+                idx.range_of(decl.name.0, decl.name.1),
+            ),
+        });
+        for (start, end) in decl.members {
+            let range = idx.range_of(start, end);
+            out.push(Pending {
+                start,
+                end,
+                sym: make_document_symbol(
+                    text[start..end].to_string(),
+                    None,
+                    SymbolKind::ENUM_MEMBER,
+                    range,
+                    range,
+                ),
+            });
+        }
+    }
+}
+
+fn line_col_of_match(text: &str, byte_idx: usize) -> (u32, u32) {
+    let pos = LineIndex::new(text).to_pos(byte_idx);
+    (pos.line, pos.character)
 }
 
 fn collect_document_symbols(
@@ -337,18 +1601,26 @@ fn collect_document_symbols(
             node.start_byte(),
             node.end_byte()
         );
-        if let Some(kind) = kind_for(node.kind()) {
+        if let Some(kind) = kind_for_node(node) {
             if let Some(name) = name_node(node) {
                 let name_start = name.start_byte();
-                let name_end = name.end_byte();
+                let (name_end, _) = module_path(node, name);
                 let selection_range = idx.range_of(name_start, name_end);
                 let range = idx.range_of(node.start_byte(), node.end_byte());
-                let label = text[name_start..name_end].to_string();
+                let label = symbol_label(node, &text[name_start..name_end]);
+                let (kind, detail) = if is_interface_stub(node) {
+                    (SymbolKind::INTERFACE, Some("interface stub".to_string()))
+                } else {
+                    (kind, detail_for(node, text))
+                };
                 out.push(Pending {
                     start: node.start_byte(),
                     end: node.end_byte(),
-                    sym: make_document_symbol(label, kind, range, selection_range),
+                    sym: make_document_symbol(label, detail, kind, range, selection_range),
                 });
+                if node.kind() == "struct_definition" {
+                    collect_field_symbols(node, text, idx, out);
+                }
             } else {
                 warn!(
                     "match without name kind={} bytes={}-{}",
@@ -368,24 +1640,63 @@ fn collect_document_symbols(
     }
 }
 
+fn collect_field_symbols(node: Node, text: &str, idx: &LineIndex, out: &mut Vec<Pending>) {
+    for (decl, name) in struct_fields(node) {
+        // With a default the whole `x::T = 1` is the field's range.
+        let field = decl
+            .parent()
+            .filter(|p| p.kind() == "assignment")
+            .unwrap_or(decl);
+        let ty = (decl.kind() == "typed_expression")
+            .then(|| decl.named_child(1))
+            .flatten()
+            .map(|t| text[t.byte_range()].to_string());
+        out.push(Pending {
+            start: field.start_byte(),
+            end: field.end_byte(),
+            sym: make_document_symbol(
+                text[name.byte_range()].to_string(),
+                ty,
+                SymbolKind::FIELD,
+                idx.range_of(field.start_byte(), field.end_byte()),
+                idx.range_of(name.start_byte(), name.end_byte()),
+            ),
+        });
+    }
+}
+
 fn collect_workspace_symbols(
     text: &str,
     idx: &LineIndex,
     cursor: &mut TreeCursor,
     uri: &Url,
-    out: &mut Vec<SymbolInformation>,
+    scope_stack: &mut Vec<String>,
+    out: &mut Vec<ExtractedSymbol>,
 ) {
     loop {
         let node = cursor.node();
-        if let Some(kind) = kind_for(node.kind()) {
-            if let Some(name) = name_node(node) {
-                let name_start = name.start_byte();
-                let name_end = name.end_byte();
-                let range = idx.range_of(node.start_byte(), node.end_byte());
-                let label = text[name_start..name_end].to_string();
-                #[allow(deprecated)]
-                {
-                    out.push(SymbolInformation {
+        let mut pushed = false;
+        if let Some(kind) = kind_for_node(node)
+            && let Some(name) = name_node(node)
+        {
+            let name_start = name.start_byte();
+            let (name_end, leaf) = module_path(node, name);
+            let range = idx.range_of(node.start_byte(), node.end_byte());
+            let label = symbol_label(node, &text[name_start..name_end]);
+            let kind = if is_interface_stub(node) {
+                SymbolKind::INTERFACE
+            } else {
+                kind
+            };
+            let signature = if kind == SymbolKind::FUNCTION {
+                detail_for(node, text)
+            } else {
+                None
+            };
+            #[allow(deprecated)]
+            {
+                out.push(ExtractedSymbol {
+                    info: SymbolInformation {
                         name: label,
                         kind,
                         tags: None::<Vec<SymbolTag>>,
@@ -394,17 +1705,342 @@ fn collect_workspace_symbols(
                             uri: uri.clone(),
                             range,
                         },
-                        container_name: None,
-                    });
-                }
+                        container_name: scope_stack.last().cloned(),
+                    },
+                    signature,
+                    supertype: supertype_name(node, text),
+                });
+            }
+            if matches!(node.kind(), "module_definition" | "struct_definition") {
+                scope_stack.push(text[leaf.byte_range()].to_string());
+                pushed = true;
             }
         }
         if cursor.goto_first_child() {
-            collect_workspace_symbols(text, idx, cursor, uri, out);
+            collect_workspace_symbols(text, idx, cursor, uri, scope_stack, out);
             cursor.goto_parent();
         }
+        if pushed {
+            scope_stack.pop();
+        }
         if !cursor.goto_next_sibling() {
             break;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use crate::state::ServerState;
+
+    const URI: &str = "file:///t.jl";
+
+    fn state(text: &str) -> ServerState {
+        let state = ServerState::default();
+        state.open_doc(URI.into(), text.into());
+        state
+    }
+
+    fn workspace_symbols(text: &str) -> Vec<(String, SymbolKind)> {
+        let state = state(text);
+        let doc = state.docs.get(URI).unwrap();
+        let uri = Url::parse(URI).unwrap();
+        extract_workspace_symbols_with_cache(&doc, &state.lang, Duration::ZERO, &uri)
+            .into_iter()
+            .map(|s| (s.info.name, s.info.kind))
+            .collect()
+    }
+
+    #[test]
+    fn collect_named_descendants_in_source_order() {
+        let tree = parse::parse("f(a, g(b), c)", None).unwrap();
+        let text = "f(a, g(b), c)";
+        let names: Vec<&str> =
+            collect_named_descendants_by(tree.root_node(), &|n: &Node| n.kind() == "identifier")
+                .into_iter()
+                .map(|n| &text[n.byte_range()])
+                .collect();
+        assert_eq!(names, ["f", "a", "g", "b", "c"]);
+        let first = find_named_descendant_by(tree.root_node(), |n: &Node| {
+            n.kind() == "identifier" && n.start_byte() > 2
+        });
+        assert_eq!(first.map(|n| &text[n.byte_range()]), Some("g"));
+    }
+
+    #[test]
+    fn highlight_marks_definitions_as_writes() {
+        let state = state("function area(r)\n    r^2\nend\narea(2) + area(3)\n");
+        let doc = state.docs.get(URI).unwrap();
+        let highlights = highlight_occurrences(&doc, &state.lang, Position::new(3, 1));
+        let kinds: Vec<_> = highlights
+            .iter()
+            .map(|h| (h.range.start.line, h.kind.unwrap()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0, DocumentHighlightKind::WRITE),
+                (3, DocumentHighlightKind::READ),
+                (3, DocumentHighlightKind::READ),
+            ]
+        );
+    }
+
+    #[test]
+    fn signature_help_reads_definitions_in_other_documents() {
+        let state = state("scale(1, \n");
+        let lib = "file:///lib.jl";
+        state.open_doc(lib.into(), "function scale(x, k)\n    x * k\nend\n".into());
+        state.reindex_doc(lib);
+        state.reindex_doc(URI);
+        let help = signature_help_at(&state, URI, Position::new(0, 9)).unwrap();
+        assert_eq!(help.signatures[0].label, "scale(x, k)");
+        assert_eq!(help.active_parameter, Some(1));
+    }
+
+    #[test]
+    fn enum_parenthesized_members_span_nested_calls() {
+        let text = "@enum Color (\n    red = f(1),\n    green,\n)\nblue = 3\n";
+        let tree = parse::parse(text, None).unwrap();
+        let decls = enum_declarations(text, Some(&tree));
+        let members: Vec<&str> = decls[0]
+            .members
+            .iter()
+            .map(|&(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(members, ["red", "green"]);
+        let (start, end) = decls[0].span;
+        assert_eq!(
+            &text[start..end],
+            "@enum Color (\n    red = f(1),\n    green,\n)"
+        );
+    }
+
+    #[test]
+    fn short_function_definitions_are_named_by_their_callee() {
+        assert_eq!(
+            workspace_symbols("f(x) = x\n"),
+            [("f".to_string(), SymbolKind::FUNCTION)]
+        );
+        assert_eq!(
+            workspace_symbols("struct T end\n(a::T)(x) = x\n"),
+            [
+                ("T".to_string(), SymbolKind::STRUCT),
+                ("T".to_string(), SymbolKind::FUNCTION),
+            ]
+        );
+        assert!(workspace_symbols("obj.field = 1\na[i] = 2\n").is_empty());
+    }
+
+    #[test]
+    fn shorthands_stop_at_their_arguments() {
+        let text = "\
+@shorthands scatter   # scatter!, too
+@shorthands histogram
+@shorthands(
+    [:bar, :bar, :end],
+    :sticks,
+)
+function plot_helper(series)
+    series
+end
+";
+        let uri = Url::parse(URI).unwrap();
+        let names: Vec<(String, u32)> = synthesize_shorthand_symbols(text, &uri)
+            .into_iter()
+            .map(|s| (s.name, s.location.range.start.line))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("scatter".to_string(), 0),
+                ("histogram".to_string(), 1),
+                ("bar".to_string(), 3),
+                ("sticks".to_string(), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn generated_functions_are_indexed_once() {
+        let text = "@generated function unroll(x)\n    :(x)\nend\n";
+        let uri = Url::parse(URI).unwrap();
+        let names: Vec<String> = synthesize_macro_symbols(text, &uri)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["unroll"]);
+        assert_eq!(
+            workspace_symbols(text),
+            [("unroll".to_string(), SymbolKind::FUNCTION)]
+        );
+    }
+
+    #[test]
+    fn doc_macro_names_the_documented_function() {
+        let text = "@doc \"Scale `x` by two.\" double\n@doc \"\"\"Halve.\"\"\" halve(x) = x / 2\n";
+        let uri = Url::parse(URI).unwrap();
+        let names: Vec<(String, u32)> = synthesize_macro_symbols(text, &uri)
+            .into_iter()
+            .map(|s| (s.name, s.location.range.start.line))
+            .collect();
+        assert_eq!(names, [("double".to_string(), 0), ("halve".to_string(), 1)]);
+        let halves = workspace_symbols(text)
+            .into_iter()
+            .filter(|(name, _)| name == "halve")
+            .count();
+        assert_eq!(halves, 1);
+    }
+
+    #[test]
+    fn dotted_module_header_is_labelled_in_full() {
+        let state = state("module A.B.C\nf() = 1\nend\n");
+        let doc = state.docs.get(URI).unwrap();
+        let uri = Url::parse(URI).unwrap();
+        let symbols: Vec<(String, Option<String>)> =
+            extract_workspace_symbols_with_cache(&doc, &state.lang, Duration::ZERO, &uri)
+                .into_iter()
+                .map(|s| (s.info.name, s.info.container_name))
+                .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("A.B.C".to_string(), None),
+                ("f".to_string(), Some("C".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn kwdef_struct_fields_are_outline_children() {
+        let state = state(include_str!("../tests/fixtures/kwdef.jl"));
+        let doc = state.docs.get(URI).unwrap();
+        let outline = extract_document_symbols_with_cache(&doc, &state.lang, Duration::ZERO);
+        let fields = |name: &str| -> Vec<(String, SymbolKind, Option<String>)> {
+            outline
+                .iter()
+                .find(|s| s.name == name)
+                .and_then(|s| s.children.clone())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|f| (f.name, f.kind, f.detail))
+                .collect()
+        };
+        let field = |name: &str, ty: Option<&str>| {
+            (name.to_string(), SymbolKind::FIELD, ty.map(str::to_string))
+        };
+        assert_eq!(
+            fields("Settings"),
+            [
+                field("verbose", Some("Bool")),
+                field("retries", Some("Int")),
+                field("timeout", None),
+                field("label", Some("String")),
+            ]
+        );
+        assert_eq!(fields("Counter"), [field("count", Some("Int"))]);
+    }
+
+    #[test]
+    fn abstract_types_show_their_supertype() {
+        let state = state(include_str!("../tests/fixtures/abstract_types.jl"));
+        let doc = state.docs.get(URI).unwrap();
+        let details: Vec<(String, SymbolKind, Option<String>)> =
+            extract_document_symbols_with_cache(&doc, &state.lang, Duration::ZERO)
+                .into_iter()
+                .filter(|s| s.kind == SymbolKind::CLASS)
+                .map(|s| (s.name, s.kind, s.detail))
+                .collect();
+        assert_eq!(
+            details,
+            [
+                ("Animal".to_string(), SymbolKind::CLASS, None),
+                (
+                    "Mammal".to_string(),
+                    SymbolKind::CLASS,
+                    Some("<: Animal".to_string())
+                ),
+                (
+                    "Dog".to_string(),
+                    SymbolKind::CLASS,
+                    Some("<: Mammal".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn documented_interface_stub() {
+        let state = state(include_str!("../tests/fixtures/interface_stub.jl"));
+        state.reindex_doc(URI);
+        {
+            let doc = state.docs.get(URI).unwrap();
+            let outline = extract_document_symbols_with_cache(&doc, &state.lang, Duration::ZERO);
+            let stub = outline.iter().find(|s| s.name == "process").unwrap();
+            assert_eq!(stub.kind, SymbolKind::INTERFACE);
+            assert_eq!(stub.detail.as_deref(), Some("interface stub"));
+        }
+        let hover = hover_at(&state, URI, Position::new(8, 10)).unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("expected markdown hover");
+        };
+        assert!(
+            markup.value.contains("*interface stub*"),
+            "{}",
+            markup.value
+        );
+        assert!(
+            markup
+                .value
+                .contains("Every concrete `Job` type implements this."),
+            "{}",
+            markup.value
+        );
+    }
+
+    #[test]
+    fn crlf_lines_keep_their_columns() {
+        let text = "module M\r\n    function area(r)\r\n    end\r\nend\r\n@userplot Pie\r\n";
+        let state = state(text);
+        let doc = state.docs.get(URI).unwrap();
+        let outline = extract_document_symbols_with_cache(&doc, &state.lang, Duration::ZERO);
+        let area = &outline[0].children.as_ref().unwrap()[0];
+        assert_eq!(area.name, "area");
+        assert_eq!(
+            area.selection_range,
+            Range::new(Position::new(1, 13), Position::new(1, 17))
+        );
+        // The module ends at `end`, before the line's `\r`.
+        assert_eq!(outline[0].range.end, Position::new(3, 3));
+
+        let uri = Url::parse(URI).unwrap();
+        let pie = &synthesize_macro_symbols(text, &uri)[0];
+        assert_eq!(pie.location.range.start, Position::new(4, 10));
+    }
+
+    #[test]
+    fn mutable_structs_are_classes() {
+        let text = "struct Foo\n    x::Int\nend\nmutable struct Bar\n    y::Int\nend\nprimitive type Word 32 end\n";
+        assert_eq!(
+            workspace_symbols(text),
+            [
+                ("Foo".to_string(), SymbolKind::STRUCT),
+                ("Bar".to_string(), SymbolKind::CLASS),
+                ("Word".to_string(), SymbolKind::STRUCT),
+            ]
+        );
+        let state = state(text);
+        let doc = state.docs.get(URI).unwrap();
+        let kinds: Vec<SymbolKind> =
+            extract_document_symbols_with_cache(&doc, &state.lang, Duration::ZERO)
+                .into_iter()
+                .map(|s| s.kind)
+                .collect();
+        assert_eq!(
+            kinds,
+            [SymbolKind::STRUCT, SymbolKind::CLASS, SymbolKind::STRUCT]
+        );
+    }
+}
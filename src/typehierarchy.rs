@@ -0,0 +1,103 @@
+use tower_lsp::lsp_types::{Position, SymbolKind, TypeHierarchyItem};
+
+use crate::definition::name_range;
+use crate::index::SymbolEntry;
+use crate::state::ServerState;
+use crate::symbols::identifier_at_position;
+
+// There is no type resolution: a supertype is whatever indexed type carries
+// the declared name, and subtypes are the types declaring this name.
+
+pub fn prepare(state: &ServerState, uri: &str, pos: Position) -> Vec<TypeHierarchyItem> {
+    let Some((name, _)) = state
+        .docs
+        .get(uri)
+        .and_then(|doc| identifier_at_position(&doc, &state.lang, pos))
+    else {
+        return Vec::new();
+    };
+    types_named(state, &name)
+        .iter()
+        .map(|e| item_for_entry(state, e))
+        .collect()
+}
+
+pub fn supertypes(state: &ServerState, item: &TypeHierarchyItem) -> Vec<TypeHierarchyItem> {
+    let Some(sup) = entry_for_item(state, item).and_then(|e| e.supertype) else {
+        return Vec::new();
+    };
+    types_named(state, &sup)
+        .iter()
+        .map(|e| item_for_entry(state, e))
+        .collect()
+}
+
+pub fn subtypes(state: &ServerState, item: &TypeHierarchyItem) -> Vec<TypeHierarchyItem> {
+    state
+        .symbols
+        .subtypes_of(&item.name)
+        .iter()
+        .filter(|e| is_type(e.kind))
+        .map(|e| item_for_entry(state, e))
+        .collect()
+}
+
+// Structs are STRUCT, mutable structs and abstract types CLASS.
+fn is_type(kind: SymbolKind) -> bool {
+    matches!(kind, SymbolKind::STRUCT | SymbolKind::CLASS)
+}
+
+fn types_named(state: &ServerState, name: &str) -> Vec<SymbolEntry> {
+    let mut entries = state.symbols.search_exact(name, &[]);
+    entries.retain(|e| is_type(e.kind));
+    entries
+}
+
+fn entry_for_item(state: &ServerState, item: &TypeHierarchyItem) -> Option<SymbolEntry> {
+    types_named(state, &item.name)
+        .into_iter()
+        .find(|e| e.uri == item.uri && e.range == item.range)
+}
+
+fn item_for_entry(state: &ServerState, entry: &SymbolEntry) -> TypeHierarchyItem {
+    TypeHierarchyItem {
+        name: entry.name.to_string(),
+        kind: entry.kind,
+        tags: None,
+        detail: entry.supertype.as_deref().map(|s| format!("<: {s}")),
+        uri: entry.uri.clone(),
+        range: entry.range,
+        selection_range: name_range(state, entry).unwrap_or(entry.range),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///t.jl";
+
+    #[test]
+    fn walks_declared_supertypes_both_ways() {
+        let state = ServerState::default();
+        state.open_doc(
+            URI.into(),
+            "abstract type A end\nstruct B <: A end\nmutable struct C <: B end\n".into(),
+        );
+        state.reindex_doc(URI);
+
+        let names = |items: Vec<TypeHierarchyItem>| -> Vec<String> {
+            items.into_iter().map(|i| i.name).collect()
+        };
+        let b = prepare(&state, URI, Position::new(1, 7));
+        assert_eq!(names(b.clone()), ["B"]);
+        assert_eq!(b[0].detail.as_deref(), Some("<: A"));
+        assert_eq!(names(supertypes(&state, &b[0])), ["A"]);
+        assert_eq!(names(subtypes(&state, &b[0])), ["C"]);
+
+        let a = prepare(&state, URI, Position::new(0, 14));
+        assert!(supertypes(&state, &a[0]).is_empty());
+        assert_eq!(names(subtypes(&state, &a[0])), ["B"]);
+    }
+}
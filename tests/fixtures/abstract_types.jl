@@ -0,0 +1,9 @@
+# Fixture for abstract type details: the outline should show `<: Animal` for
+# `Mammal`, `<: Mammal` for `Dog`, and no supertype for `Animal`.
+abstract type Animal end
+abstract type Mammal <: Animal end
+abstract type Dog <: Mammal end
+
+struct Beagle <: Dog
+    name::String
+end
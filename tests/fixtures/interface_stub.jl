@@ -0,0 +1,11 @@
+# Fixture for interface stubs: `process` is declared without methods and
+# documented, so the outline marks it as an interface stub and hover shows
+# its docstring.
+"""
+    process(job)
+
+Run `job`. Every concrete `Job` type implements this.
+"""
+function process end
+
+abstract type Job end
@@ -0,0 +1,13 @@
+# Fixture for struct field symbols: the outline should list `Settings` with
+# the fields `verbose`, `retries`, `timeout` and `label`, whether or not they
+# have defaults.
+Base.@kwdef struct Settings
+    verbose::Bool = false
+    retries::Int = 3
+    timeout = 2.5
+    label::String
+end
+
+@kwdef mutable struct Counter
+    count::Int = 0
+end
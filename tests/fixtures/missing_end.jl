@@ -0,0 +1,7 @@
+# Fixture for the unbalanced-block diagnostics: `scale` is never closed, so
+# a missing `end` is reported for the `function` on line 3.
+function scale(v, k)
+    for i in eachindex(v)
+        v[i] *= k
+    end
+    return v